@@ -10,7 +10,7 @@ use core::sync::atomic::Ordering;
 use crate::*;
 
 // Like folly's implementation, we use a time a based check to run reclamation about every
-// `SYNC_TIME_PERIOD` nanoseconds. The next time we should run reclamation is stored in
+// `sync_time_period` nanoseconds. The next time we should run reclamation is stored in
 // `due_time` inside `Domain`. On `no_std` we don't (yet) have access to time so this feature is
 // disabled. Also on platforms with < 64 bits, we can only store 2^32 nanoseconds -> ~4 seconds or
 // less, so this feature is also disabled. Additionally, loom can't support time for reasons of
@@ -30,10 +30,108 @@ const HCOUNT_MULTIPLIER: isize = 2;
 const NUM_SHARDS: usize = 2;
 #[cfg(not(loom))]
 const NUM_SHARDS: usize = 8;
+// Every domain has room for this many shards, the configured number of them is used. A macro so
+// the limit can also be spliced into the assert message of the const DomainConfig::shards
+#[cfg(loom)]
+macro_rules! max_shards {
+    () => {
+        2
+    };
+}
+#[cfg(not(loom))]
+macro_rules! max_shards {
+    () => {
+        64
+    };
+}
+const MAX_SHARDS: usize = max_shards!();
 const IGNORED_LOW_BITS: u8 = 8;
-const SHARD_MASK: usize = NUM_SHARDS - 1;
 const LOCK_BIT: usize = 1;
 
+/// Tuning parameters for a [`Domain`], used with [`Domain::with_config`] or
+/// [`Domain::set_config`].
+///
+/// ```
+/// use haphazard::{Domain, DomainConfig};
+///
+/// // Reclaim after every 64 retires, for large objects that should not pile up
+/// Domain::global().set_config(DomainConfig::new().retire_threshold(64));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DomainConfig {
+    rcount_threshold: isize,
+    hcount_multiplier: isize,
+    shards: usize,
+    #[cfg(all(feature = "std", target_pointer_width = "64", not(loom)))]
+    sync_time_period: u64,
+}
+
+impl Default for DomainConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DomainConfig {
+    /// The configuration every domain starts out with.
+    pub const fn new() -> Self {
+        DomainConfig {
+            rcount_threshold: RCOUNT_THRESHOLD,
+            hcount_multiplier: HCOUNT_MULTIPLIER,
+            shards: NUM_SHARDS,
+            #[cfg(all(feature = "std", target_pointer_width = "64", not(loom)))]
+            sync_time_period: SYNC_TIME_PERIOD,
+        }
+    }
+
+    /// Reclamation runs once more objects than this have been retired since the last one.
+    ///
+    /// The threshold is raised to the hazard pointer multiplier times the number of hazard
+    /// pointers if that is higher.
+    pub const fn retire_threshold(mut self, threshold: usize) -> Self {
+        assert!(threshold <= isize::MAX as usize, "retire threshold too large");
+        self.rcount_threshold = threshold as isize;
+        self
+    }
+
+    /// How many retired objects per hazard pointer are allowed before reclamation runs.
+    pub const fn hazard_pointer_multiplier(mut self, multiplier: usize) -> Self {
+        assert!(
+            multiplier <= isize::MAX as usize,
+            "hazard pointer multiplier too large"
+        );
+        self.hcount_multiplier = multiplier as isize;
+        self
+    }
+
+    /// Number of lists retired objects are spread over to reduce contention.
+    ///
+    /// Must be a power of two no larger than 64, or 2 when built for loom.
+    pub const fn shards(mut self, shards: usize) -> Self {
+        assert!(
+            shards.is_power_of_two() && shards <= MAX_SHARDS,
+            concat!(
+                "the number of shards must be a power of two no larger than ",
+                max_shards!()
+            )
+        );
+        self.shards = shards;
+        self
+    }
+
+    /// Reclamation also runs when this much time has passed since the last one, no matter how
+    /// few objects were retired.
+    #[cfg(all(feature = "std", target_pointer_width = "64", not(loom)))]
+    pub const fn sync_time_period(mut self, period: std::time::Duration) -> Self {
+        assert!(
+            period.as_nanos() <= u64::MAX as u128,
+            "sync time period too large"
+        );
+        self.sync_time_period = period.as_nanos() as u64;
+        self
+    }
+}
+
 /// The singleton [domain family](Domain) for the global domain.
 ///
 /// The global domain is a convenient way to amortize the overhead of memory reclamation across
@@ -148,10 +246,17 @@ impl<T> WithMut<T> for core::sync::atomic::AtomicPtr<T> {
 /// the guarded data is reclaimed when your data structure is dropped.
 pub struct Domain<F> {
     hazptrs: HazPtrRecords,
-    untagged: [RetiredList; NUM_SHARDS],
+    untagged: [RetiredList; MAX_SHARDS],
     family: PhantomData<F>,
     #[cfg(all(feature = "std", target_pointer_width = "64", not(loom)))]
     due_time: AtomicU64,
+    rcount_threshold: AtomicIsize,
+    hcount_multiplier: AtomicIsize,
+    shard_mask: AtomicUsize,
+    // The most shards ever configured, retired objects can be in any of them
+    max_shards: AtomicUsize,
+    #[cfg(all(feature = "std", target_pointer_width = "64", not(loom)))]
+    sync_time_period: AtomicU64,
     nbulk_reclaims: AtomicUsize,
     count: AtomicIsize,
     shutdown: bool,
@@ -349,7 +454,7 @@ pub fn static_unique_domain_cannot_retire_pointer_in_different_domain() {}
 
 // Macro to make new const only when not in loom.
 macro_rules! new {
-    ($($decl:tt)*) => {
+    ($($const:ident)?) => {
         /// Construct a new domain with the given family type.
         ///
         /// The type checker protects you from accidentally using a `HazardPointer` from one domain
@@ -359,18 +464,25 @@ macro_rules! new {
         /// possible, since they guarantee a unique `F` for every domain.
         ///
         /// See the [`Domain`] documentation for more details.
-        pub $($decl)*(_: &'_ F) -> Self {
+        pub $($const)? fn new(family: &'_ F) -> Self {
+            Self::with_config(family, DomainConfig::new())
+        }
+
+        /// Construct a new domain with the given family type and configuration.
+        ///
+        /// See [`Domain::new`] for more details.
+        pub $($const)? fn with_config(_: &'_ F, config: DomainConfig) -> Self {
             // https://blog.rust-lang.org/2021/02/11/Rust-1.50.0.html#const-value-repetition-for-arrays
             #[cfg(not(loom))]
             let untagged = {
                 // https://github.com/rust-lang/rust-clippy/issues/7665
                 #[allow(clippy::declare_interior_mutable_const)]
                 const RETIRED_LIST: RetiredList = RetiredList::new();
-                [RETIRED_LIST; NUM_SHARDS]
+                [RETIRED_LIST; MAX_SHARDS]
             };
             #[cfg(loom)]
             let untagged = {
-                [(); NUM_SHARDS].map(|_| RetiredList::new())
+                [(); MAX_SHARDS].map(|_| RetiredList::new())
             };
             Self {
                 hazptrs: HazPtrRecords {
@@ -382,6 +494,12 @@ macro_rules! new {
                 count: AtomicIsize::new(0),
                 #[cfg(all(feature = "std", target_pointer_width = "64", not(loom)))]
                 due_time: AtomicU64::new(0),
+                rcount_threshold: AtomicIsize::new(config.rcount_threshold),
+                hcount_multiplier: AtomicIsize::new(config.hcount_multiplier),
+                shard_mask: AtomicUsize::new(config.shards - 1),
+                max_shards: AtomicUsize::new(config.shards),
+                #[cfg(all(feature = "std", target_pointer_width = "64", not(loom)))]
+                sync_time_period: AtomicU64::new(config.sync_time_period),
                 nbulk_reclaims: AtomicUsize::new(0),
                 family: PhantomData,
                 shutdown: false,
@@ -392,9 +510,28 @@ macro_rules! new {
 
 impl<F> Domain<F> {
     #[cfg(not(loom))]
-    new!(const fn new);
+    new!(const);
     #[cfg(loom)]
-    new!(fn new);
+    new!();
+
+    /// Replaces the configuration of the domain, for example to tune the global domain at
+    /// startup.
+    ///
+    /// Takes effect for objects retired from now on, a lower number of shards leaves the objects
+    /// in the other shards to be reclaimed as usual.
+    pub fn set_config(&self, config: DomainConfig) {
+        self.rcount_threshold
+            .store(config.rcount_threshold, Ordering::Release);
+        self.hcount_multiplier
+            .store(config.hcount_multiplier, Ordering::Release);
+        // Reclamation has to look at every shard ever used, so that count is raised before
+        // anything is retired with the new mask
+        self.max_shards.fetch_max(config.shards, Ordering::AcqRel);
+        self.shard_mask.store(config.shards - 1, Ordering::Release);
+        #[cfg(all(feature = "std", target_pointer_width = "64", not(loom)))]
+        self.sync_time_period
+            .store(config.sync_time_period, Ordering::Release);
+    }
 
     pub(crate) fn acquire(&self) -> &HazPtrRecord {
         self.acquire_many::<1>()[0]
@@ -637,14 +774,16 @@ impl<F> Domain<F> {
         crate::asymmetric_light_barrier();
 
         let retired = Box::into_raw(retired);
-        unsafe { self.untagged[Self::calc_shard(retired)].push(retired, retired) };
+        unsafe { self.untagged[self.calc_shard(retired)].push(retired, retired) };
         self.count.fetch_add(1, Ordering::Release);
 
         self.check_threshold_and_reclaim()
     }
 
     fn threshold(&self) -> isize {
-        RCOUNT_THRESHOLD.max(HCOUNT_MULTIPLIER * self.hazptrs.count.load(Ordering::Acquire))
+        let threshold = self.rcount_threshold.load(Ordering::Acquire);
+        let multiplier = self.hcount_multiplier.load(Ordering::Acquire);
+        threshold.max(multiplier * self.hazptrs.count.load(Ordering::Acquire))
     }
 
     fn check_count_threshold(&self) -> isize {
@@ -658,7 +797,7 @@ impl<F> Domain<F> {
                     #[cfg(all(feature = "std", target_pointer_width = "64", not(loom)))]
                     {
                         self.due_time
-                            .store(Self::now() + SYNC_TIME_PERIOD, Ordering::Release);
+                            .store(Self::now() + self.sync_time_period(), Ordering::Release);
                     }
                     return rcount;
                 }
@@ -677,7 +816,7 @@ impl<F> Domain<F> {
                 .due_time
                 .compare_exchange(
                     due,
-                    time + SYNC_TIME_PERIOD,
+                    time + self.sync_time_period(),
                     Ordering::AcqRel,
                     Ordering::Relaxed,
                 )
@@ -689,6 +828,11 @@ impl<F> Domain<F> {
         self.count.swap(0, Ordering::AcqRel)
     }
 
+    #[cfg(all(feature = "std", target_pointer_width = "64", not(loom)))]
+    fn sync_time_period(&self) -> u64 {
+        self.sync_time_period.load(Ordering::Acquire)
+    }
+
     fn check_threshold_and_reclaim(&self) -> usize {
         #[allow(unused_mut)]
         let mut rcount = self.check_count_threshold();
@@ -713,9 +857,10 @@ impl<F> Domain<F> {
         let mut total_reclaimed = 0;
         loop {
             let mut done = true;
-            let mut stolen_heads = [core::ptr::null_mut(); NUM_SHARDS];
+            let mut stolen_heads = [core::ptr::null_mut(); MAX_SHARDS];
             let mut empty = true;
-            for (stolen_head, untagged) in stolen_heads.iter_mut().zip(&self.untagged) {
+            let shards = &self.untagged[..self.max_shards.load(Ordering::Acquire)];
+            for (stolen_head, untagged) in stolen_heads.iter_mut().zip(shards) {
                 *stolen_head = untagged.pop_all();
                 if !stolen_head.is_null() {
                     empty = false;
@@ -759,7 +904,7 @@ impl<F> Domain<F> {
 
     fn match_reclaim_untagged(
         &self,
-        stolen_heads: [*mut Retired; NUM_SHARDS],
+        stolen_heads: [*mut Retired; MAX_SHARDS],
         guarded_ptrs: &BTreeSet<*mut u8>,
     ) -> (usize, bool) {
         let mut unreclaimed = core::ptr::null_mut();
@@ -852,7 +997,7 @@ impl<F> Domain<F> {
     }
 
     fn reclaim_all_objects(&mut self) {
-        for i in 0..NUM_SHARDS {
+        for i in 0..MAX_SHARDS {
             let head = self.untagged[i].pop_all();
             // Safety: &mut self implies that there are no active Hazard Pointers.
             // So, all objects are safe to reclaim.
@@ -897,13 +1042,13 @@ impl<F> Domain<F> {
     }
 
     #[cfg(not(loom))]
-    fn calc_shard(input: *mut Retired) -> usize {
-        (input as usize >> IGNORED_LOW_BITS) & SHARD_MASK
+    fn calc_shard(&self, input: *mut Retired) -> usize {
+        (input as usize >> IGNORED_LOW_BITS) & self.shard_mask.load(Ordering::Acquire)
     }
 
     #[cfg(loom)]
-    fn calc_shard(_input: *mut Retired) -> usize {
-        SHARD.fetch_add(1, Ordering::Relaxed) & SHARD_MASK
+    fn calc_shard(&self, _input: *mut Retired) -> usize {
+        SHARD.fetch_add(1, Ordering::Relaxed) & self.shard_mask.load(Ordering::Acquire)
     }
 }
 
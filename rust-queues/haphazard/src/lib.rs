@@ -143,13 +143,63 @@ mod pointer;
 mod record;
 mod sync;
 
+// The asymmetric barriers let the common path, protecting a pointer, get away with just a compiler
+// fence, while the rare path, reclamation, forces a full barrier on every thread of the process
+// with membarrier. Without membarrier both sides issue a full fence. Which one is used is decided
+// once, as the two sides have to agree.
+const BARRIERS_UNDECIDED: u8 = 0;
+const BARRIERS_MEMBARRIER: u8 = 1;
+const BARRIERS_SYMMETRIC: u8 = 2;
+
+// Not a loom atomic, loom always gets the symmetric barriers
+static BARRIERS: core::sync::atomic::AtomicU8 =
+    core::sync::atomic::AtomicU8::new(BARRIERS_UNDECIDED);
+
+fn barriers() -> u8 {
+    let current = BARRIERS.load(Ordering::Acquire);
+    if current != BARRIERS_UNDECIDED {
+        return current;
+    }
+    let chosen = if membarrier::register() {
+        BARRIERS_MEMBARRIER
+    } else {
+        BARRIERS_SYMMETRIC
+    };
+    match BARRIERS.compare_exchange(
+        BARRIERS_UNDECIDED,
+        chosen,
+        Ordering::AcqRel,
+        Ordering::Acquire,
+    ) {
+        Ok(_) => chosen,
+        Err(current) => current,
+    }
+}
+
+/// Makes hazard pointers use a full fence both when protecting and when reclaiming, instead of
+/// membarrier on Linux.
+///
+/// This only takes effect if called before the first pointer is protected or retired, the return
+/// value tells whether the symmetric barriers are in use.
+pub fn use_symmetric_barriers() -> bool {
+    match BARRIERS.compare_exchange(
+        BARRIERS_UNDECIDED,
+        BARRIERS_SYMMETRIC,
+        Ordering::AcqRel,
+        Ordering::Acquire,
+    ) {
+        Ok(_) => true,
+        Err(current) => current == BARRIERS_SYMMETRIC,
+    }
+}
+
 fn asymmetric_light_barrier() {
-    // TODO: if cfg!(linux) {
     // https://github.com/facebook/folly/blob/bd600cd4e88f664f285489c76b6ad835d8367cd2/folly/portability/Asm.h#L28
-    //
-    // Lilly edit: Turning this of is technicly unsound, but fine with hows we use the library in
-    // the project. But DO NOT use it for anything else. Memfences are there for a reason
-    //crate::sync::atomic::fence(core::sync::atomic::Ordering::SeqCst);
+    if barriers() == BARRIERS_MEMBARRIER {
+        core::sync::atomic::compiler_fence(Ordering::SeqCst);
+    } else {
+        crate::sync::atomic::fence(Ordering::SeqCst);
+    }
 }
 
 enum HeavyBarrierKind {
@@ -158,16 +208,64 @@ enum HeavyBarrierKind {
 }
 
 fn asymmetric_heavy_barrier(_: HeavyBarrierKind) {
-    // TODO: if cfg!(linux) {
     // https://github.com/facebook/folly/blob/bd600cd4e88f664f285489c76b6ad835d8367cd2/folly/synchronization/AsymmetricMemoryBarrier.cpp#L84
     //
-    // Same here as above, wildly unsafe in general, fine (probably) for us
-    //crate::sync::atomic::fence(core::sync::atomic::Ordering::SeqCst);
+    // Only the private expedited command is registered for, so it is used for both kinds
+    if barriers() == BARRIERS_MEMBARRIER {
+        membarrier::private_expedited();
+    } else {
+        crate::sync::atomic::fence(Ordering::SeqCst);
+    }
+}
+
+#[cfg(all(target_os = "linux", not(loom), not(miri)))]
+mod membarrier {
+    use std::os::raw::{c_int, c_long};
+
+    // From linux/membarrier.h
+    const MEMBARRIER_CMD_QUERY: c_int = 0;
+    const MEMBARRIER_CMD_PRIVATE_EXPEDITED: c_int = 1 << 3;
+    const MEMBARRIER_CMD_REGISTER_PRIVATE_EXPEDITED: c_int = 1 << 4;
+
+    fn membarrier(cmd: c_int) -> c_long {
+        // Safety: membarrier takes no pointers
+        unsafe { libc::syscall(libc::SYS_membarrier, cmd, 0 as c_int) }
+    }
+
+    /// Registers the process for private expedited membarriers, returns false if the kernel does
+    /// not support them.
+    pub(crate) fn register() -> bool {
+        let supported = membarrier(MEMBARRIER_CMD_QUERY);
+        if supported < 0 || supported & MEMBARRIER_CMD_PRIVATE_EXPEDITED as c_long == 0 {
+            return false;
+        }
+        membarrier(MEMBARRIER_CMD_REGISTER_PRIVATE_EXPEDITED) == 0
+    }
+
+    pub(crate) fn private_expedited() {
+        // Can not fail once registered, and continuing without the barrier would be unsound
+        assert_eq!(
+            membarrier(MEMBARRIER_CMD_PRIVATE_EXPEDITED),
+            0,
+            "membarrier failed after registering for it"
+        );
+    }
+}
+
+#[cfg(not(all(target_os = "linux", not(loom), not(miri))))]
+mod membarrier {
+    pub(crate) fn register() -> bool {
+        false
+    }
+
+    pub(crate) fn private_expedited() {
+        unreachable!("membarrier is only used on Linux")
+    }
 }
 
 /// Raw building blocks for managing hazard pointers.
 pub mod raw {
-    pub use crate::domain::Domain;
+    pub use crate::domain::{Domain, DomainConfig};
     /// Well-known domain families.
     pub mod families {
         pub use crate::domain::Global;
@@ -183,6 +281,7 @@ use core::ptr::NonNull;
 use core::sync::atomic::Ordering;
 
 pub use domain::Domain;
+pub use domain::DomainConfig;
 pub use domain::Global;
 pub use domain::Singleton;
 pub use hazard::{HazardPointer, HazardPointerArray};
@@ -0,0 +1,20 @@
+use haphazard::*;
+
+// The barriers are decided once per process, so this is the only test in this file
+#[test]
+fn symmetric_barriers_before_first_use() {
+    assert!(use_symmetric_barriers());
+
+    let x = AtomicPtr::from(Box::new(42));
+    let mut h = HazardPointer::new();
+    assert_eq!(*x.safe_load(&mut h).unwrap(), 42);
+    let old = x.swap(Box::new(43)).unwrap();
+    unsafe { old.retire() };
+    assert_eq!(Domain::global().eager_reclaim(), 0);
+    drop(h);
+    assert_eq!(Domain::global().eager_reclaim(), 1);
+
+    // Already decided, so this has no effect
+    assert!(use_symmetric_barriers());
+    unsafe { x.retire() };
+}
@@ -233,3 +233,47 @@ fn hazardptr_compare_exchange_fail() {
 
     let _ = unsafe { Box::from_raw(not_current) };
 }
+
+#[test]
+fn reclaims_at_configured_threshold() {
+    let drops = Arc::new(AtomicUsize::new(0));
+
+    let domain = Domain::with_config(&(), DomainConfig::new().retire_threshold(4).shards(1));
+
+    // The first retire is always past the due time
+    let x = Box::into_raw(Box::new(CountDrops(Arc::clone(&drops))));
+    let n = unsafe { domain.retire_ptr::<_, Box<_>>(x) };
+    assert_eq!(n, 1);
+    drops.store(0, Ordering::SeqCst);
+
+    // After that, reclamation runs once more than the threshold has been retired
+    for _ in 0..4 {
+        let x = Box::into_raw(Box::new(CountDrops(Arc::clone(&drops))));
+        let n = unsafe { domain.retire_ptr::<_, Box<_>>(x) };
+        assert_eq!(n, 0);
+    }
+    assert_eq!(drops.load(Ordering::SeqCst), 0);
+    let x = Box::into_raw(Box::new(CountDrops(Arc::clone(&drops))));
+    let n = unsafe { domain.retire_ptr::<_, Box<_>>(x) };
+    assert_eq!(n, 5);
+    assert_eq!(drops.load(Ordering::SeqCst), 5);
+
+    // A lower threshold applies to the following retires
+    domain.set_config(DomainConfig::new().retire_threshold(0).shards(2));
+    let x = Box::into_raw(Box::new(CountDrops(Arc::clone(&drops))));
+    let n = unsafe { domain.retire_ptr::<_, Box<_>>(x) };
+    assert_eq!(n, 1);
+    assert_eq!(drops.load(Ordering::SeqCst), 6);
+}
+
+#[test]
+#[should_panic(expected = "power of two")]
+fn shards_must_be_a_power_of_two() {
+    let _ = DomainConfig::new().shards(3);
+}
+
+#[test]
+#[should_panic(expected = "no larger than 64")]
+fn shards_are_limited() {
+    let _ = DomainConfig::new().shards(128);
+}
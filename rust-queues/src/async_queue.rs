@@ -0,0 +1,231 @@
+use std::{
+    future,
+    marker::PhantomData,
+    sync::{
+        atomic::{fence, AtomicUsize, Ordering::SeqCst},
+        Arc, Mutex,
+    },
+    task::{Context, Poll, Waker},
+};
+
+use crate::shared_queue::SharedQueue;
+
+/// Wraps any [`SharedQueue`] so consumers can await items from inside an async executor.
+///
+/// A receiver that finds the queue empty registers its waker, and the next enqueue wakes every
+/// registered receiver. They then race for the item and the losers register again. Each clone is
+/// a separate handle, in the same way as the handles of the wrapped queue.
+pub struct AsyncQueue<T, Q> {
+    queue: Q,
+    waiters: Arc<Waiters>,
+    _marker: PhantomData<fn(T) -> T>,
+}
+
+struct Waiters {
+    count: AtomicUsize,
+    wakers: Mutex<Vec<Waker>>,
+}
+
+impl<T, Q> AsyncQueue<T, Q>
+where
+    Q: SharedQueue<T>,
+{
+    pub fn from_queue(queue: Q) -> Self {
+        Self {
+            queue,
+            waiters: Arc::new(Waiters {
+                count: AtomicUsize::new(0),
+                wakers: Mutex::new(Vec::new()),
+            }),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Dequeues an item, waiting until one is enqueued if the queue is empty
+    pub async fn recv(&mut self) -> T {
+        future::poll_fn(|cx| self.poll_recv(cx)).await
+    }
+
+    pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<T> {
+        if let Some(val) = self.queue.dequeue() {
+            return Poll::Ready(val);
+        }
+
+        {
+            let mut wakers = self.waiters.wakers.lock().unwrap();
+            if !wakers.iter().any(|w| w.will_wake(cx.waker())) {
+                wakers.push(cx.waker().clone());
+                self.waiters.count.fetch_add(1, SeqCst);
+            }
+        }
+        // Pairs with the fence in enqueue, either the producer sees our waker or we see its item.
+        // If we do get an item the waker stays registered, which only causes a spurious wake
+        fence(SeqCst);
+        match self.queue.dequeue() {
+            Some(val) => Poll::Ready(val),
+            None => Poll::Pending,
+        }
+    }
+}
+
+impl<T, Q> SharedQueue<T> for AsyncQueue<T, Q>
+where
+    Q: SharedQueue<T>,
+{
+    fn new() -> Self {
+        Self::from_queue(Q::new())
+    }
+
+    fn enqueue(&mut self, val: T) {
+        self.queue.enqueue(val);
+        fence(SeqCst);
+        if self.waiters.count.load(SeqCst) > 0 {
+            let wakers = {
+                let mut wakers = self.waiters.wakers.lock().unwrap();
+                self.waiters.count.store(0, SeqCst);
+                std::mem::take(&mut *wakers)
+            };
+            // Wake outside the lock, the woken task may poll again on this thread
+            for waker in wakers {
+                waker.wake();
+            }
+        }
+    }
+
+    // Never waits, use recv to wait for an item
+    fn dequeue(&mut self) -> Option<T> {
+        self.queue.dequeue()
+    }
+}
+
+impl<T, Q> Clone for AsyncQueue<T, Q>
+where
+    Q: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            queue: self.queue.clone(),
+            waiters: self.waiters.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        future::Future,
+        pin::pin,
+        sync::Arc,
+        task::{Context, Poll, Wake, Waker},
+        thread::{self, Thread},
+        time::Duration,
+    };
+
+    use super::AsyncQueue;
+    use crate::{lprq::lprq::SharedLPRQ, ms_queue::msq_hazp::MSQueue, shared_queue::SharedQueue};
+
+    // Minimal single future executor, parks the thread until the future's waker is called
+    struct ThreadWaker(Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    fn block_on<F: Future>(fut: F) -> F::Output {
+        let mut fut = pin!(fut);
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            match fut.as_mut().poll(&mut cx) {
+                Poll::Ready(val) => return val,
+                Poll::Pending => thread::park(),
+            }
+        }
+    }
+
+    #[test]
+    fn recv_ready() {
+        let mut queue: AsyncQueue<i32, MSQueue<i32>> = AsyncQueue::new();
+        queue.enqueue(1);
+        queue.enqueue(2);
+        assert_eq!(block_on(queue.recv()), 1);
+        assert_eq!(block_on(queue.recv()), 2);
+    }
+
+    #[test]
+    fn pending_until_enqueue() {
+        let mut queue: AsyncQueue<i32, SharedLPRQ<i32>> = AsyncQueue::new();
+        let mut producer = queue.clone();
+
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        assert_eq!(queue.poll_recv(&mut cx), Poll::Pending);
+        producer.enqueue(3);
+        assert_eq!(queue.poll_recv(&mut cx), Poll::Ready(3));
+    }
+
+    #[test]
+    fn recv_woken_by_other_thread() {
+        let queue: AsyncQueue<i32, SharedLPRQ<i32>> = AsyncQueue::new();
+        let mut producer = queue.clone();
+        let handle = thread::spawn(move || {
+            for i in 0..100 {
+                producer.enqueue(i);
+                if i % 10 == 0 {
+                    thread::sleep(Duration::from_millis(1));
+                }
+            }
+        });
+
+        let mut consumer = queue.clone();
+        let sum = block_on(async {
+            let mut sum = 0;
+            for _ in 0..100 {
+                sum += consumer.recv().await;
+            }
+            sum
+        });
+        handle.join().unwrap();
+        assert_eq!(sum, 100 * 99 / 2);
+    }
+
+    #[test]
+    fn recv_concurrent() {
+        let queue: AsyncQueue<usize, MSQueue<usize>> = AsyncQueue::new();
+
+        let mut consumers = vec![];
+        for _ in 0..4 {
+            let mut queue = queue.clone();
+            let handle = thread::spawn(move || {
+                block_on(async {
+                    let mut sum = 0;
+                    for _ in 0..100 {
+                        sum += queue.recv().await;
+                    }
+                    sum
+                })
+            });
+            consumers.push(handle);
+        }
+
+        let mut producers = vec![];
+        for i in 0..4 {
+            let mut queue = queue.clone();
+            let handle = thread::spawn(move || {
+                for j in 0..100 {
+                    queue.enqueue(i * 100 + j);
+                }
+            });
+            producers.push(handle);
+        }
+
+        for handle in producers {
+            handle.join().unwrap();
+        }
+        let sum: usize = consumers.into_iter().map(|h| h.join().unwrap()).sum();
+        assert_eq!(sum, 400 * 399 / 2, "Sums do not match!");
+    }
+}
@@ -0,0 +1,90 @@
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering::SeqCst;
+use std::sync::Arc;
+use std::thread;
+
+use crate::core_utils;
+use crate::shared_queue::BatchQueue;
+
+use core_affinity;
+
+const BASE: usize = 10;
+
+/// Same as the mpmc benchmark, but producers enqueue and consumers dequeue `batch_size` items
+/// at a time
+pub fn benchmark<Q>(
+    nproducer: usize,
+    nconsumer: usize,
+    logn: usize,
+    even_cores_only: bool,
+    batch_size: usize,
+    queue: Q,
+) where
+    Q: BatchQueue<i32> + Clone + Send + 'static,
+{
+    let stop_flag = Arc::new(AtomicBool::new(false));
+
+    let mut producer_handles = vec![];
+    let mut consumer_handles = vec![];
+
+    // Calculate number of operations
+    let nops = BASE.pow(logn as u32);
+    let tops = nops / nproducer;
+
+    let binding = core_utils::get_cores(even_cores_only);
+    let mut core_ids = binding.iter();
+
+    // Producers
+    for _ in 0..nproducer {
+        let mut queue_handle = queue.clone();
+        let core_id = *core_ids
+            .next()
+            .expect("Ran out of cores! Maybe used fewer threads");
+        let handle = thread::spawn(move || {
+            let _ = core_affinity::set_for_current(core_id);
+
+            let mut j = 0;
+            while j < tops {
+                let end = (j + batch_size).min(tops);
+                queue_handle.enqueue_batch((j..end).map(|v| v as i32).collect());
+                j = end;
+            }
+        });
+        producer_handles.push(handle);
+    }
+
+    // Consumers
+    for _ in 0..nconsumer {
+        let mut queue_handle = queue.clone();
+        let stop_flag_handle = stop_flag.clone();
+        let core_id = *core_ids
+            .next()
+            .expect("Ran out of cores! Maybe used fewer threads");
+        let handle = thread::spawn(move || {
+            let _ = core_affinity::set_for_current(core_id);
+            let mut out = Vec::with_capacity(batch_size);
+
+            loop {
+                out.clear();
+                if queue_handle.dequeue_batch(&mut out, batch_size) == 0 {
+                    if stop_flag_handle.load(SeqCst) {
+                        break;
+                    }
+                    thread::yield_now();
+                }
+            }
+        });
+        consumer_handles.push(handle);
+    }
+
+    for p in producer_handles {
+        p.join().unwrap();
+    }
+
+    // Notify consumers no more elements will be enqueued
+    stop_flag.store(true, SeqCst);
+
+    for c in consumer_handles {
+        c.join().unwrap();
+    }
+}
@@ -1,24 +1,77 @@
 use std::env::{self};
 
+use crate::lprq::lprq::DEFAULT_RING_SIZE;
+
 /// Default exponent for # operations
 const LOGN_OPS: usize = 7;
 
+/// Default number of items per batch operation
+const BATCH_SIZE: usize = 32;
+
+/// Sets the ring size of queues made of ring segments, it can be given anywhere in the arguments
+const RING_SIZE_FLAG: &str = "--ring-size";
+
 pub enum BenchmarkType {
     /// (Threads, logn, even_only)
     Pairwise(usize, usize, bool, f32),
 
     /// (Producers, consumers, logn, even_only)
     Mpmc(usize, usize, usize, bool, f32),
+
+    /// (Producers, consumers, logn, even_only, batch_size)
+    Batch(usize, usize, usize, bool, usize),
+}
+
+// The arguments without the ring size flag and its value
+fn positional_args() -> Vec<String> {
+    let mut args = env::args();
+    let mut positional = vec![];
+    while let Some(arg) = args.next() {
+        if arg == RING_SIZE_FLAG {
+            args.next();
+        } else {
+            positional.push(arg);
+        }
+    }
+    positional
+}
+
+/// Number of cells in the ring segments of the queue, set with `--ring-size <size>`.
+pub fn ring_size() -> usize {
+    let mut args = env::args().skip_while(|arg| arg != RING_SIZE_FLAG).skip(1);
+    let ring_size: usize = match args.next() {
+        Some(size) => size.parse().expect("Ring size must be positive"),
+        None => DEFAULT_RING_SIZE,
+    };
+    if !ring_size.is_power_of_two() {
+        eprintln!("Ring size must be a power of two.");
+        std::process::exit(1);
+    }
+    println!("  Ring size: {}", ring_size);
+    ring_size
+}
+
+/// For queues whose ring size is a compile time constant. Rejects `--ring-size` instead of
+/// silently ignoring it, and prints the size that is used.
+pub fn fixed_ring_size(ring_size: usize) {
+    if env::args().any(|arg| arg == RING_SIZE_FLAG) {
+        eprintln!(
+            "This queue has a fixed ring size of {}, {} is not supported.",
+            ring_size, RING_SIZE_FLAG
+        );
+        std::process::exit(1);
+    }
+    println!("  Ring size: {}", ring_size);
 }
 
 pub fn parse_args(benchmark: &str) -> BenchmarkType {
-    let args: Vec<String> = env::args().collect();
+    let args: Vec<String> = positional_args();
 
     match benchmark {
         "pairwise" => {
             if args.len() < 3 {
                 eprintln!(
-                    "Usage: {} <threads> [exponent_base_ten] [even_cores_only] [congestion_factor]",
+                    "Usage: {} <threads> [exponent_base_ten] [even_cores_only] [congestion_factor] [--ring-size <size>]",
                     args[0]
                 );
                 std::process::exit(1);
@@ -62,7 +115,7 @@ pub fn parse_args(benchmark: &str) -> BenchmarkType {
         "mpmc" => {
             if args.len() < 3 {
                 eprintln!(
-                    "Usage for mpmc: {} <producers> <consumers> [logn] [even_cores_only]",
+                    "Usage for mpmc: {} <producers> <consumers> [logn] [even_cores_only] [--ring-size <size>]",
                     args[0]
                 );
                 std::process::exit(1);
@@ -112,8 +165,65 @@ pub fn parse_args(benchmark: &str) -> BenchmarkType {
             BenchmarkType::Mpmc(producers, consumers, logn, even_only, congestion_factor)
         }
 
+        "batch" => {
+            if args.len() < 3 {
+                eprintln!(
+                    "Usage for batch: {} <producers> <consumers> [logn] [even_cores_only] [batch_size] [--ring-size <size>]",
+                    args[0]
+                );
+                std::process::exit(1);
+            }
+
+            let producers: usize = args[1]
+                .parse()
+                .expect("Number of producers must be a positive");
+            if producers == 0 {
+                eprintln!("Number of producers cannot be 0.");
+                std::process::exit(1);
+            }
+
+            let consumers: usize = args[2]
+                .parse()
+                .expect("Number of consumers must be a positive");
+            if consumers == 0 {
+                eprintln!("Number of consumers cannot be 0.");
+                std::process::exit(1);
+            }
+
+            let logn: usize = if args.len() > 3 {
+                args[3].parse().expect("Exponent must be positive")
+            } else {
+                LOGN_OPS
+            };
+
+            let even_only: bool = if args.len() > 4 {
+                args[4].parse().expect("Valid values: true, false")
+            } else {
+                false
+            };
+            let batch_size: usize = if args.len() > 5 {
+                args[5].parse().expect("Batch size must be positive")
+            } else {
+                BATCH_SIZE
+            };
+            if batch_size == 0 {
+                eprintln!("Batch size cannot be 0.");
+                std::process::exit(1);
+            }
+
+            println!("===========================================");
+            println!("  Benchmark: {}", args[0]);
+            println!("  Producers: {}", producers);
+            println!("  Consumers: {}", consumers);
+            println!("  Operations: 10^{}", logn);
+            println!("  Even cores only: {}", even_only);
+            println!("  Batch size: {}", batch_size);
+
+            BenchmarkType::Batch(producers, consumers, logn, even_only, batch_size)
+        }
+
         _ => {
-            eprintln!("Invalid benchmark type. Must be either 'pairwise' or 'mpmc'.");
+            eprintln!("Invalid benchmark type. Must be either 'pairwise', 'mpmc' or 'batch'.");
             std::process::exit(1);
         }
     }
@@ -0,0 +1,21 @@
+use rust_queues::benchmark_utils::{self, BenchmarkType::Pairwise};
+use rust_queues::ccqueue::ccqueue::CCQueue;
+use rust_queues::pairwise_benchmark;
+use rust_queues::shared_queue::SharedQueue;
+
+fn main() {
+    let benchmark = benchmark_utils::parse_args("pairwise");
+
+    let (threads, logn, even_only, congestion_factor) = match benchmark {
+        Pairwise(threads, logn, even_only, congestion_factor) => {
+            (threads, logn, even_only, congestion_factor)
+        }
+        _ => panic!("Expected a 'Pairwise' benchmark type"),
+    };
+
+    let queue: CCQueue<i32> = CCQueue::new();
+
+    pairwise_benchmark::benchmark(threads, logn, even_only, congestion_factor, queue);
+
+    println!("  Finished");
+}
@@ -0,0 +1,23 @@
+use rust_queues::benchmark_utils::{self, BenchmarkType::Pairwise};
+use rust_queues::faa_array_queue::FAAArrayQueue;
+use rust_queues::pairwise_benchmark;
+use rust_queues::shared_queue::SharedQueue;
+
+fn main() {
+    let benchmark = benchmark_utils::parse_args("pairwise");
+
+    let (threads, logn, even_only, congestion_factor) = match benchmark {
+        Pairwise(threads, logn, even_only, congestion_factor) => {
+            (threads, logn, even_only, congestion_factor)
+        }
+        _ => panic!("Expected a 'Pairwise' benchmark type"),
+    };
+
+    const RING_SIZE: usize = 1024;
+    benchmark_utils::fixed_ring_size(RING_SIZE);
+    let queue: FAAArrayQueue<'_, i32, RING_SIZE> = FAAArrayQueue::new();
+
+    pairwise_benchmark::benchmark(threads, logn, even_only, congestion_factor, queue);
+
+    println!("  Finished");
+}
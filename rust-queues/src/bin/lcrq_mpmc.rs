@@ -0,0 +1,41 @@
+// LCRQ relies on cmpxchg16b, so the queue only exists on x86_64
+#[cfg(target_arch = "x86_64")]
+use rust_queues::{
+    benchmark_utils::{self, BenchmarkType::Mpmc},
+    lcrq::lcrq::SharedLCRQ,
+    mpmc_benchmark,
+    shared_queue::SharedQueue,
+};
+
+#[cfg(not(target_arch = "x86_64"))]
+fn main() {
+    eprintln!("LCRQ needs cmpxchg16b and is only available on x86_64");
+    std::process::exit(1);
+}
+
+#[cfg(target_arch = "x86_64")]
+fn main() {
+    let benchmark = benchmark_utils::parse_args("mpmc");
+
+    let (producers, consumers, logn, even_only, congestion_factor) = match benchmark {
+        Mpmc(producers, consumers, logn, even_only, congestion_factor) => {
+            (producers, consumers, logn, even_only, congestion_factor)
+        }
+        _ => panic!("Expected a 'Mpmc' benchmark type"),
+    };
+
+    const RING_SIZE: usize = 1024;
+    benchmark_utils::fixed_ring_size(RING_SIZE);
+    let queue: SharedLCRQ<'_, i32, RING_SIZE> = SharedLCRQ::new();
+
+    mpmc_benchmark::benchmark(
+        producers,
+        consumers,
+        logn,
+        even_only,
+        congestion_factor,
+        queue,
+    );
+
+    println!("  Finished");
+}
@@ -0,0 +1,20 @@
+use rust_queues::batch_benchmark;
+use rust_queues::benchmark_utils::{self, BenchmarkType::Batch};
+use rust_queues::lprq::lprq::SharedLPRQ;
+
+fn main() {
+    let benchmark = benchmark_utils::parse_args("batch");
+
+    let (producers, consumers, logn, even_only, batch_size) = match benchmark {
+        Batch(producers, consumers, logn, even_only, batch_size) => {
+            (producers, consumers, logn, even_only, batch_size)
+        }
+        _ => panic!("Expected a 'Batch' benchmark type"),
+    };
+
+    let queue: SharedLPRQ<i32> = SharedLPRQ::with_ring_size(benchmark_utils::ring_size());
+
+    batch_benchmark::benchmark(producers, consumers, logn, even_only, batch_size, queue);
+
+    println!("  Finished");
+}
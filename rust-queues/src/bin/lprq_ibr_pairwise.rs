@@ -0,0 +1,20 @@
+use rust_queues::benchmark_utils::{self, BenchmarkType::Pairwise};
+use rust_queues::lprq::ibr_lprq::SharedLPRQ;
+use rust_queues::pairwise_benchmark;
+
+fn main() {
+    let benchmark = benchmark_utils::parse_args("pairwise");
+
+    let (threads, logn, even_only, congestion_factor) = match benchmark {
+        Pairwise(threads, logn, even_only, congestion_factor) => {
+            (threads, logn, even_only, congestion_factor)
+        }
+        _ => panic!("Expected a 'Pairwise' benchmark type"),
+    };
+
+    let queue: SharedLPRQ<i32> = SharedLPRQ::with_ring_size(benchmark_utils::ring_size());
+
+    pairwise_benchmark::benchmark(threads, logn, even_only, congestion_factor, queue);
+
+    println!("  Finished");
+}
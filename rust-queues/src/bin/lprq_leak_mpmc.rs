@@ -1,7 +1,6 @@
 use rust_queues::benchmark_utils::{self, BenchmarkType::Mpmc};
-use rust_queues::lprq::leak_lprq::lprq::SharedLPRQ;
+use rust_queues::lprq::leak_lprq::SharedLPRQ;
 use rust_queues::mpmc_benchmark;
-use rust_queues::shared_queue::SharedQueue;
 
 fn main() {
     let benchmark = benchmark_utils::parse_args("mpmc");
@@ -13,7 +12,7 @@ fn main() {
         _ => panic!("Expected a 'Mpmc' benchmark type"),
     };
 
-    let queue: SharedLPRQ<i32, 1024> = SharedLPRQ::new();
+    let queue: SharedLPRQ<i32> = SharedLPRQ::with_ring_size(benchmark_utils::ring_size());
 
     mpmc_benchmark::benchmark(
         producers,
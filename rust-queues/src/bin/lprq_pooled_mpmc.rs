@@ -0,0 +1,31 @@
+use rust_queues::benchmark_utils::{self, BenchmarkType::Mpmc};
+use rust_queues::lprq::lprq::SharedLPRQ;
+use rust_queues::mpmc_benchmark;
+
+// Reclaimed segments kept for reuse
+const SEGMENT_POOL_CAPACITY: usize = 16;
+
+fn main() {
+    let benchmark = benchmark_utils::parse_args("mpmc");
+
+    let (producers, consumers, logn, even_only, congestion_factor) = match benchmark {
+        Mpmc(producers, consumers, logn, even_only, congestion_factor) => {
+            (producers, consumers, logn, even_only, congestion_factor)
+        }
+        _ => panic!("Expected a 'Mpmc' benchmark type"),
+    };
+
+    let queue: SharedLPRQ<i32> =
+        SharedLPRQ::with_segment_pool(benchmark_utils::ring_size(), SEGMENT_POOL_CAPACITY);
+
+    mpmc_benchmark::benchmark(
+        producers,
+        consumers,
+        logn,
+        even_only,
+        congestion_factor,
+        queue,
+    );
+
+    println!("  Finished");
+}
@@ -0,0 +1,29 @@
+use rust_queues::benchmark_utils::{self, BenchmarkType::Mpmc};
+use rust_queues::lprq::{layout::Remapped, lprq::SharedLPRQ};
+use rust_queues::mpmc_benchmark;
+use rust_queues::reclaim::HazardPointers;
+
+fn main() {
+    let benchmark = benchmark_utils::parse_args("mpmc");
+
+    let (producers, consumers, logn, even_only, congestion_factor) = match benchmark {
+        Mpmc(producers, consumers, logn, even_only, congestion_factor) => {
+            (producers, consumers, logn, even_only, congestion_factor)
+        }
+        _ => panic!("Expected a 'Mpmc' benchmark type"),
+    };
+
+    let queue: SharedLPRQ<i32, HazardPointers, Remapped> =
+        SharedLPRQ::with_ring_size(benchmark_utils::ring_size());
+
+    mpmc_benchmark::benchmark(
+        producers,
+        consumers,
+        logn,
+        even_only,
+        congestion_factor,
+        queue,
+    );
+
+    println!("  Finished");
+}
@@ -0,0 +1,22 @@
+use rust_queues::benchmark_utils::{self, BenchmarkType::Pairwise};
+use rust_queues::lprq::{layout::Remapped, lprq::SharedLPRQ};
+use rust_queues::pairwise_benchmark;
+use rust_queues::reclaim::HazardPointers;
+
+fn main() {
+    let benchmark = benchmark_utils::parse_args("pairwise");
+
+    let (threads, logn, even_only, congestion_factor) = match benchmark {
+        Pairwise(threads, logn, even_only, congestion_factor) => {
+            (threads, logn, even_only, congestion_factor)
+        }
+        _ => panic!("Expected a 'Pairwise' benchmark type"),
+    };
+
+    let queue: SharedLPRQ<i32, HazardPointers, Remapped> =
+        SharedLPRQ::with_ring_size(benchmark_utils::ring_size());
+
+    pairwise_benchmark::benchmark(threads, logn, even_only, congestion_factor, queue);
+
+    println!("  Finished");
+}
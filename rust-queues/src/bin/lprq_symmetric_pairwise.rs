@@ -0,0 +1,26 @@
+use rust_queues::benchmark_utils::{self, BenchmarkType::Pairwise};
+use rust_queues::lprq::lprq::SharedLPRQ;
+use rust_queues::pairwise_benchmark;
+
+fn main() {
+    // Full fences on both sides of the hazard pointers, to compare against membarrier
+    assert!(
+        haphazard::use_symmetric_barriers(),
+        "The barriers were decided before the benchmark started"
+    );
+
+    let benchmark = benchmark_utils::parse_args("pairwise");
+
+    let (threads, logn, even_only, congestion_factor) = match benchmark {
+        Pairwise(threads, logn, even_only, congestion_factor) => {
+            (threads, logn, even_only, congestion_factor)
+        }
+        _ => panic!("Expected a 'Pairwise' benchmark type"),
+    };
+
+    let queue: SharedLPRQ<i32> = SharedLPRQ::with_ring_size(benchmark_utils::ring_size());
+
+    pairwise_benchmark::benchmark(threads, logn, even_only, congestion_factor, queue);
+
+    println!("  Finished");
+}
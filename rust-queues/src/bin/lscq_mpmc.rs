@@ -0,0 +1,30 @@
+use rust_queues::benchmark_utils::{self, BenchmarkType::Mpmc};
+use rust_queues::lscq::lscq::SharedLSCQ;
+use rust_queues::mpmc_benchmark;
+use rust_queues::shared_queue::SharedQueue;
+
+fn main() {
+    let benchmark = benchmark_utils::parse_args("mpmc");
+
+    let (producers, consumers, logn, even_only, congestion_factor) = match benchmark {
+        Mpmc(producers, consumers, logn, even_only, congestion_factor) => {
+            (producers, consumers, logn, even_only, congestion_factor)
+        }
+        _ => panic!("Expected a 'Mpmc' benchmark type"),
+    };
+
+    const RING_SIZE: usize = 1024;
+    benchmark_utils::fixed_ring_size(RING_SIZE);
+    let queue: SharedLSCQ<'_, i32, RING_SIZE> = SharedLSCQ::new();
+
+    mpmc_benchmark::benchmark(
+        producers,
+        consumers,
+        logn,
+        even_only,
+        congestion_factor,
+        queue,
+    );
+
+    println!("  Finished");
+}
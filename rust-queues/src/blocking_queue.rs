@@ -0,0 +1,206 @@
+use std::{
+    hint,
+    marker::PhantomData,
+    sync::{
+        atomic::{fence, AtomicUsize, Ordering::SeqCst},
+        Arc, Condvar, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::shared_queue::SharedQueue;
+
+/// Number of dequeue attempts before a consumer goes to sleep
+const SPIN_LIMIT: usize = 64;
+
+/// Wraps any [`SharedQueue`] so consumers can wait for items instead of spinning.
+///
+/// Consumers spin for a short while and then park on a condition variable, producers only take
+/// the lock to wake them when someone is actually sleeping. Each clone is a separate handle, in
+/// the same way as the handles of the wrapped queue.
+pub struct BlockingQueue<T, Q> {
+    queue: Q,
+    sleepers: Arc<Sleepers>,
+    _marker: PhantomData<fn(T) -> T>,
+}
+
+struct Sleepers {
+    count: AtomicUsize,
+    lock: Mutex<()>,
+    condvar: Condvar,
+}
+
+impl<T, Q> BlockingQueue<T, Q>
+where
+    Q: SharedQueue<T>,
+{
+    pub fn from_queue(queue: Q) -> Self {
+        Self {
+            queue,
+            sleepers: Arc::new(Sleepers {
+                count: AtomicUsize::new(0),
+                lock: Mutex::new(()),
+                condvar: Condvar::new(),
+            }),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Dequeues an item, waiting for as long as it takes for one to be enqueued
+    pub fn dequeue_blocking(&mut self) -> T {
+        loop {
+            if let Some(val) = self.dequeue_until(None) {
+                return val;
+            }
+        }
+    }
+
+    /// Dequeues an item, waiting at most `timeout` for one to be enqueued
+    pub fn dequeue_timeout(&mut self, timeout: Duration) -> Option<T> {
+        self.dequeue_until(Some(Instant::now() + timeout))
+    }
+
+    fn dequeue_until(&mut self, deadline: Option<Instant>) -> Option<T> {
+        for _ in 0..SPIN_LIMIT {
+            if let Some(val) = self.queue.dequeue() {
+                return Some(val);
+            }
+            hint::spin_loop();
+        }
+
+        let mut guard = self.sleepers.lock.lock().unwrap();
+        self.sleepers.count.fetch_add(1, SeqCst);
+        // Pairs with the fence in enqueue, either the producer sees us sleeping or we see its item
+        fence(SeqCst);
+        let val = loop {
+            if let Some(val) = self.queue.dequeue() {
+                break Some(val);
+            }
+            match deadline {
+                None => guard = self.sleepers.condvar.wait(guard).unwrap(),
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        break None;
+                    }
+                    guard = self
+                        .sleepers
+                        .condvar
+                        .wait_timeout(guard, deadline - now)
+                        .unwrap()
+                        .0;
+                }
+            }
+        };
+        self.sleepers.count.fetch_sub(1, SeqCst);
+        val
+    }
+}
+
+impl<T, Q> SharedQueue<T> for BlockingQueue<T, Q>
+where
+    Q: SharedQueue<T>,
+{
+    fn new() -> Self {
+        Self::from_queue(Q::new())
+    }
+
+    fn enqueue(&mut self, val: T) {
+        self.queue.enqueue(val);
+        fence(SeqCst);
+        if self.sleepers.count.load(SeqCst) > 0 {
+            // Taking the lock makes sure the sleeper is either waiting or has not checked the
+            // queue yet, so the notification can not get lost
+            let _guard = self.sleepers.lock.lock().unwrap();
+            self.sleepers.condvar.notify_one();
+        }
+    }
+
+    // Never blocks, use dequeue_blocking or dequeue_timeout to wait for an item
+    fn dequeue(&mut self) -> Option<T> {
+        self.queue.dequeue()
+    }
+}
+
+impl<T, Q> Clone for BlockingQueue<T, Q>
+where
+    Q: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            queue: self.queue.clone(),
+            sleepers: self.sleepers.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use super::BlockingQueue;
+    use crate::{lprq::lprq::SharedLPRQ, ms_queue::msq_hazp::MSQueue, shared_queue::SharedQueue};
+
+    #[test]
+    fn timeout_on_empty() {
+        let mut queue: BlockingQueue<i32, MSQueue<i32>> = BlockingQueue::new();
+        let start = Instant::now();
+        assert_eq!(queue.dequeue_timeout(Duration::from_millis(50)), None);
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        queue.enqueue(1);
+        assert_eq!(queue.dequeue_timeout(Duration::from_millis(50)), Some(1));
+    }
+
+    #[test]
+    fn wakes_sleeping_consumer() {
+        let queue: BlockingQueue<i32, SharedLPRQ<i32>> = BlockingQueue::new();
+        let mut consumer = queue.clone();
+        let handle = thread::spawn(move || consumer.dequeue_blocking());
+        thread::sleep(Duration::from_millis(50));
+        let mut producer = queue.clone();
+        producer.enqueue(7);
+        assert_eq!(handle.join().unwrap(), 7);
+    }
+
+    #[test]
+    fn blocking_concurrent() {
+        let queue: BlockingQueue<usize, SharedLPRQ<usize>> = BlockingQueue::new();
+
+        let mut consumers = vec![];
+        for _ in 0..4 {
+            let mut queue = queue.clone();
+            let handle = thread::spawn(move || {
+                let mut sum = 0;
+                for _ in 0..100 {
+                    sum += queue.dequeue_blocking();
+                }
+                sum
+            });
+            consumers.push(handle);
+        }
+
+        let mut producers = vec![];
+        for i in 0..4 {
+            let mut queue = queue.clone();
+            let handle = thread::spawn(move || {
+                for j in 0..100 {
+                    queue.enqueue(i * 100 + j);
+                    if j % 10 == 0 {
+                        thread::sleep(Duration::from_millis(1));
+                    }
+                }
+            });
+            producers.push(handle);
+        }
+
+        for handle in producers {
+            handle.join().unwrap();
+        }
+        let sum: usize = consumers.into_iter().map(|h| h.join().unwrap()).sum();
+        assert_eq!(sum, 400 * 399 / 2, "Sums do not match!");
+    }
+}
@@ -0,0 +1,226 @@
+use std::{
+    ptr::{self, null_mut},
+    sync::{
+        atomic::{AtomicPtr, Ordering::SeqCst},
+        Arc,
+    },
+};
+
+use crossbeam_utils::CachePadded;
+
+use crate::shared_queue::SharedQueue;
+
+use super::ccsynch::{CcSynch, CcSynchHandle};
+
+// Port of ccqueue.c: a sequential linked list queue with a dummy head, where enqueues and dequeues
+// are each serialised by their own CcSynch so the two ends can proceed in parallel
+
+type Dequeued<T> = Option<(Box<Node<T>>, T)>;
+
+pub struct CCQueue<T> {
+    queue: Arc<Queue<T>>,
+    enq: CcSynchHandle<Box<Node<T>>, ()>,
+    deq: CcSynchHandle<(), Dequeued<T>>,
+    // The previous dummy node from a dequeue, reused by the next enqueue
+    spare: Option<Box<Node<T>>>,
+}
+
+impl<T> Clone for CCQueue<T> {
+    fn clone(&self) -> Self {
+        Self {
+            queue: self.queue.clone(),
+            enq: CcSynchHandle::new(),
+            deq: CcSynchHandle::new(),
+            spare: None,
+        }
+    }
+}
+
+impl<T> SharedQueue<T> for CCQueue<T>
+where
+    T: Send,
+{
+    fn new() -> Self {
+        Self {
+            queue: Arc::new(Queue::new()),
+            enq: CcSynchHandle::new(),
+            deq: CcSynchHandle::new(),
+            spare: None,
+        }
+    }
+
+    fn enqueue(&mut self, val: T) {
+        let node = match self.spare.take() {
+            Some(mut node) => {
+                node.next.store(null_mut(), SeqCst);
+                node.value = Some(val);
+                node
+            }
+            None => Box::new(Node::new(Some(val))),
+        };
+        self.queue.enq.apply(&mut self.enq, node);
+    }
+
+    fn dequeue(&mut self) -> Option<T> {
+        let (node, value) = self.queue.deq.apply(&mut self.deq, ())?;
+        if self.spare.is_none() {
+            self.spare = Some(node);
+        }
+        Some(value)
+    }
+}
+
+struct Node<T> {
+    next: CachePadded<AtomicPtr<Node<T>>>,
+    value: Option<T>,
+}
+
+impl<T> Node<T> {
+    fn new(value: Option<T>) -> Node<T> {
+        Node {
+            next: AtomicPtr::new(null_mut()).into(),
+            value,
+        }
+    }
+}
+
+// The ends of the list, each only touched by the combiner of its CcSynch
+struct Head<T>(*mut Node<T>);
+struct Tail<T>(*mut Node<T>);
+
+unsafe impl<T: Send> Send for Head<T> {}
+unsafe impl<T: Send> Send for Tail<T> {}
+
+fn serial_enqueue<T>(tail: &mut Tail<T>, node: Box<Node<T>>) {
+    let node = Box::into_raw(node);
+    // Safety: The tail node is only freed once a dequeuer has seen its next pointer set
+    unsafe { (*tail.0).next.store(node, SeqCst) };
+    tail.0 = node;
+}
+
+fn serial_dequeue<T>(head: &mut Head<T>, _: ()) -> Dequeued<T> {
+    let next = unsafe { (*head.0).next.load(SeqCst) };
+    if next.is_null() {
+        return None;
+    }
+    // Safety: The enqueue combiner may still be setting the next pointer of the new dummy, so only
+    // its value is touched here
+    let value = unsafe { (*ptr::addr_of_mut!((*next).value)).take() }
+        .expect("A node in a CCQueue had no value");
+    let old = std::mem::replace(&mut head.0, next);
+    // Safety: The old dummy is no longer reachable from either end
+    Some((unsafe { Box::from_raw(old) }, value))
+}
+
+pub struct Queue<T> {
+    enq: CachePadded<CcSynch<Tail<T>, Box<Node<T>>, ()>>,
+    deq: CachePadded<CcSynch<Head<T>, (), Dequeued<T>>>,
+}
+
+impl<T> Drop for Queue<T> {
+    fn drop(&mut self) {
+        // We have exclusive access, so free the list directly together with the values that were
+        // never dequeued
+        let mut current = self.deq.get_mut().0;
+        while !current.is_null() {
+            let node = unsafe { Box::from_raw(current) };
+            current = node.next.load(SeqCst);
+        }
+    }
+}
+
+impl<T> Default for Queue<T>
+where
+    T: Send,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Queue<T>
+where
+    T: Send,
+{
+    pub fn new() -> Queue<T> {
+        let dummy = Box::into_raw(Box::new(Node::new(None)));
+        Queue {
+            enq: CcSynch::new(Tail(dummy), serial_enqueue).into(),
+            deq: CcSynch::new(Head(dummy), serial_dequeue).into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{sync::Arc, thread};
+
+    use super::CCQueue;
+    use crate::shared_queue::SharedQueue;
+
+    #[test]
+    fn basic() {
+        let mut queue: CCQueue<i32> = CCQueue::new();
+        assert_eq!(queue.dequeue(), None);
+        for i in 0..100 {
+            queue.enqueue(i);
+        }
+        for i in 0..100 {
+            assert_eq!(queue.dequeue(), Some(i));
+        }
+        assert_eq!(queue.dequeue(), None);
+    }
+
+    #[test]
+    fn basic_concurrent() {
+        let queue: CCQueue<usize> = CCQueue::new();
+
+        let mut producers = vec![];
+        for i in 0..4 {
+            let mut queue = queue.clone();
+            let handle = thread::spawn(move || {
+                for j in 0..100 {
+                    queue.enqueue(i * 100 + j);
+                }
+            });
+            producers.push(handle);
+        }
+
+        let mut consumers = vec![];
+        for _ in 0..4 {
+            let mut queue = queue.clone();
+            let handle = thread::spawn(move || {
+                let mut sum = 0;
+                for _ in 0..100 {
+                    loop {
+                        if let Some(v) = queue.dequeue() {
+                            sum += v;
+                            break;
+                        }
+                    }
+                }
+                sum
+            });
+            consumers.push(handle);
+        }
+
+        for handle in producers {
+            handle.join().unwrap();
+        }
+        let sum: usize = consumers.into_iter().map(|h| h.join().unwrap()).sum();
+        assert_eq!(sum, 400 * 399 / 2, "Sums do not match!");
+    }
+
+    #[test]
+    fn dropping_drops_leftover_items() {
+        let item = Arc::new(());
+        let mut queue: CCQueue<Arc<()>> = CCQueue::new();
+        for _ in 0..25 {
+            queue.enqueue(Arc::clone(&item));
+        }
+        queue.dequeue().unwrap();
+        assert_eq!(Arc::strong_count(&item), 25);
+        drop(queue);
+        assert_eq!(Arc::strong_count(&item), 1);
+    }
+}
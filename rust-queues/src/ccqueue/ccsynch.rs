@@ -0,0 +1,226 @@
+use crossbeam_utils::CachePadded;
+use std::{
+    cell::UnsafeCell,
+    hint,
+    ptr::null_mut,
+    sync::atomic::{AtomicPtr, AtomicU8, Ordering::SeqCst},
+};
+
+// Port of CC-Synch from ccsynch.h in the C reference. Threads queue up their operations in a list
+// and whoever is at the front applies a batch of them to the sequential state on everyone's behalf
+
+// The owner of the node is still waiting for its operation to be applied
+const WAIT: u8 = 0;
+// The owner of the node is the next combiner
+const READY: u8 = 1;
+// The operation in the node has been applied and its result is in the node
+const DONE: u8 = 3;
+
+// How many operations of other threads a combiner applies before handing over
+const HELP_BOUND: usize = 256;
+
+struct Node<Op, R> {
+    next: CachePadded<AtomicPtr<Node<Op, R>>>,
+    // Written by the owner before it links in the next node, read by the combiner after it has
+    // seen that link
+    op: UnsafeCell<Option<Op>>,
+    // Written by the combiner before it marks the node done
+    result: UnsafeCell<Option<R>>,
+    status: CachePadded<AtomicU8>,
+}
+
+impl<Op, R> Node<Op, R> {
+    fn new(status: u8) -> *mut Node<Op, R> {
+        Box::into_raw(Box::new(Node {
+            next: AtomicPtr::new(null_mut()).into(),
+            op: UnsafeCell::new(None),
+            result: UnsafeCell::new(None),
+            status: AtomicU8::new(status).into(),
+        }))
+    }
+}
+
+/// Wraps a sequential state so operations on it can be applied from many threads with
+/// [`CcSynch::apply`]. Each thread needs its own [`CcSynchHandle`].
+pub struct CcSynch<S, Op, R> {
+    tail: CachePadded<AtomicPtr<Node<Op, R>>>,
+    state: UnsafeCell<S>,
+    apply: fn(&mut S, Op) -> R,
+}
+
+// The state is only ever accessed by the current combiner, which may be any thread, and
+// operations and results are handed between threads
+unsafe impl<S: Send, Op: Send, R: Send> Send for CcSynch<S, Op, R> {}
+unsafe impl<S: Send, Op: Send, R: Send> Sync for CcSynch<S, Op, R> {}
+
+impl<S, Op, R> Drop for CcSynch<S, Op, R> {
+    fn drop(&mut self) {
+        // No operation is in flight, so the tail is the only node left that we own
+        drop(unsafe { Box::from_raw(self.tail.load(SeqCst)) });
+    }
+}
+
+impl<S, Op, R> CcSynch<S, Op, R> {
+    pub fn new(state: S, apply: fn(&mut S, Op) -> R) -> Self {
+        CcSynch {
+            tail: AtomicPtr::new(Node::new(READY)).into(),
+            state: UnsafeCell::new(state),
+            apply,
+        }
+    }
+
+    pub fn get_mut(&mut self) -> &mut S {
+        self.state.get_mut()
+    }
+
+    /// Applies `op` to the state, either directly or by waiting for another thread to do it, and
+    /// returns its result.
+    pub fn apply(&self, handle: &mut CcSynchHandle<Op, R>, op: Op) -> R {
+        let next = handle.next;
+        // Safety: The handle's node is ours until it is swapped in as the tail
+        unsafe {
+            (*next).next.store(null_mut(), SeqCst);
+            (*next).status.store(WAIT, SeqCst);
+        }
+
+        // We take over the previous tail, it holds our operation and is our node after this call
+        let curr = self.tail.swap(next, SeqCst);
+        handle.next = curr;
+        // Safety: Nodes are only freed by their handle or the CcSynch, neither of which can happen
+        // while we are in here
+        let curr = unsafe { &*curr };
+
+        let mut status = curr.status.load(SeqCst);
+        let op = if status == WAIT {
+            unsafe { *curr.op.get() = Some(op) };
+            curr.next.store(next, SeqCst);
+
+            while status == WAIT {
+                hint::spin_loop();
+                status = curr.status.load(SeqCst);
+            }
+            if status == DONE {
+                return unsafe { (*curr.result.get()).take() }
+                    .expect("A CcSynch node was marked done without a result");
+            }
+            // Nobody applied it, we are the combiner now
+            unsafe { (*curr.op.get()).take() }
+                .expect("A CcSynch node was handed over without its operation")
+        } else {
+            op
+        };
+
+        // Safety: Only the combiner accesses the state, and there is only one at a time
+        let state = unsafe { &mut *self.state.get() };
+        let result = (self.apply)(state, op);
+
+        let mut curr = next;
+        let mut next = unsafe { (*curr).next.load(SeqCst) };
+        let mut count = 0;
+        while !next.is_null() && count < HELP_BOUND {
+            count += 1;
+            // Safety: The owner of curr waits until we mark it done, and has stored its operation
+            // before linking in next
+            let node = unsafe { &*curr };
+            let op = unsafe { (*node.op.get()).take() }
+                .expect("A CcSynch node was linked in without its operation");
+            unsafe { *node.result.get() = Some((self.apply)(state, op)) };
+            node.status.store(DONE, SeqCst);
+
+            curr = next;
+            next = unsafe { (*curr).next.load(SeqCst) };
+        }
+        // Hand over to the owner of the first node we did not get to
+        unsafe { (*curr).status.store(READY, SeqCst) };
+
+        result
+    }
+}
+
+/// Per thread state for [`CcSynch::apply`]. It holds the node used for the next operation, which
+/// is swapped for another one on every call.
+pub struct CcSynchHandle<Op, R> {
+    next: *mut Node<Op, R>,
+}
+
+// The node is only accessed by others while an operation is in flight, which it never is when the
+// handle is moved
+unsafe impl<Op: Send, R: Send> Send for CcSynchHandle<Op, R> {}
+
+impl<Op, R> Default for CcSynchHandle<Op, R> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Op, R> CcSynchHandle<Op, R> {
+    pub fn new() -> Self {
+        CcSynchHandle {
+            next: Node::new(WAIT),
+        }
+    }
+}
+
+impl<Op, R> Drop for CcSynchHandle<Op, R> {
+    fn drop(&mut self) {
+        drop(unsafe { Box::from_raw(self.next) });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{sync::Arc, thread};
+
+    use super::{CcSynch, CcSynchHandle};
+
+    #[test]
+    fn basic() {
+        let synch: CcSynch<Vec<i32>, i32, usize> = CcSynch::new(Vec::new(), |v, x| {
+            v.push(x);
+            v.len()
+        });
+        let mut handle = CcSynchHandle::new();
+        for i in 0..10 {
+            assert_eq!(synch.apply(&mut handle, i), i as usize + 1);
+        }
+    }
+
+    #[test]
+    fn basic_concurrent() {
+        let mut synch: CcSynch<usize, usize, usize> = CcSynch::new(0, |sum, x| {
+            *sum += x;
+            *sum
+        });
+        let synch_ref = &synch;
+
+        thread::scope(|s| {
+            for i in 0..4 {
+                s.spawn(move || {
+                    let mut handle = CcSynchHandle::new();
+                    for j in 0..100 {
+                        synch_ref.apply(&mut handle, i * 100 + j);
+                    }
+                });
+            }
+        });
+
+        assert_eq!(*synch.get_mut(), 400 * 399 / 2, "Sums do not match!");
+    }
+
+    #[test]
+    fn drops_results_and_state() {
+        let item = Arc::new(());
+        let synch: CcSynch<Vec<Arc<()>>, Arc<()>, Arc<()>> = CcSynch::new(Vec::new(), |v, x| {
+            v.push(Arc::clone(&x));
+            x
+        });
+        let mut handle = CcSynchHandle::new();
+        for _ in 0..10 {
+            drop(synch.apply(&mut handle, Arc::clone(&item)));
+        }
+        assert_eq!(Arc::strong_count(&item), 11);
+        drop(synch);
+        drop(handle);
+        assert_eq!(Arc::strong_count(&item), 1);
+    }
+}
@@ -0,0 +1,334 @@
+use crate::shared_queue::SharedQueue;
+use crossbeam_utils::CachePadded;
+use haphazard::{AtomicPtr, HazardPointer};
+use std::{
+    array, hint,
+    ptr::{self, null_mut},
+    sync::{
+        atomic::{self, AtomicUsize, Ordering::SeqCst},
+        Arc,
+    },
+};
+
+// Only its address is used, to mark cells whose value has been taken or that were skipped by a
+// dequeuer. No value can ever be stored at it
+static TAKEN: u8 = 0;
+
+pub struct FAAArrayQueue<'a, T, const N: usize> {
+    queue: Arc<Queue<T, N>>,
+    hazard: HazardPointer<'a>,
+}
+
+impl<T, const N: usize> Clone for FAAArrayQueue<'_, T, N> {
+    fn clone(&self) -> Self {
+        Self {
+            queue: self.queue.clone(),
+            hazard: HazardPointer::new(),
+        }
+    }
+}
+
+impl<T, const N: usize> SharedQueue<T> for FAAArrayQueue<'_, T, N>
+where
+    T: Send,
+{
+    fn new() -> Self {
+        FAAArrayQueue {
+            queue: Arc::new(Queue::new()),
+            hazard: HazardPointer::new(),
+        }
+    }
+
+    fn enqueue(&mut self, val: T) {
+        self.queue.enqueue(val, &mut self.hazard)
+    }
+
+    fn dequeue(&mut self) -> Option<T> {
+        self.queue.dequeue(&mut self.hazard)
+    }
+}
+
+struct Node<T, const N: usize> {
+    deq_idx: CachePadded<AtomicUsize>,
+    enq_idx: CachePadded<AtomicUsize>,
+    next: CachePadded<AtomicPtr<Node<T, N>>>,
+    // Boxed values, null until an enqueuer claims the cell and TAKEN once a dequeuer has been there
+    items: [CachePadded<atomic::AtomicPtr<T>>; N],
+}
+// Unsafe impls of send and sync, a value is only ever taken out by the thread whose swap returned
+// it, and the queue itself is only Send/Sync when T is Send
+unsafe impl<T, const N: usize> Sync for Node<T, N> {}
+unsafe impl<T, const N: usize> Send for Node<T, N> {}
+
+impl<T, const N: usize> Node<T, N> {
+    // A node whose first cell already holds the value, so the enqueuer that links it in is done
+    fn new(value: *mut T) -> Node<T, N> {
+        let node = Node::empty();
+        node.items[0].store(value, SeqCst);
+        node.enq_idx.store(1, SeqCst);
+        node
+    }
+    fn empty() -> Node<T, N> {
+        Node {
+            deq_idx: AtomicUsize::new(0).into(),
+            enq_idx: AtomicUsize::new(0).into(),
+            next: unsafe { AtomicPtr::new(null_mut()) }.into(),
+            items: array::from_fn(|_| atomic::AtomicPtr::new(null_mut()).into()),
+        }
+    }
+}
+
+pub struct Queue<T, const N: usize> {
+    head: CachePadded<AtomicPtr<Node<T, N>>>,
+    tail: CachePadded<AtomicPtr<Node<T, N>>>,
+}
+
+unsafe impl<T: Send, const N: usize> Send for Queue<T, N> {}
+unsafe impl<T: Send, const N: usize> Sync for Queue<T, N> {}
+
+impl<T, const N: usize> Drop for Queue<T, N> {
+    fn drop(&mut self) {
+        // We have exclusive access, so walk the list and free every node directly, together with
+        // the values that were never dequeued
+        let taken = Self::taken();
+        let mut current = self.head.load_ptr();
+        while !current.is_null() {
+            let node = unsafe { Box::from_raw(current) };
+            for item in &node.items {
+                let value = item.load(SeqCst);
+                if !value.is_null() && value != taken {
+                    drop(unsafe { Box::from_raw(value) });
+                }
+            }
+            current = node.next.load_ptr();
+        }
+    }
+}
+
+impl<T, const N: usize> Default for Queue<T, N>
+where
+    T: Send,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize> Queue<T, N> {
+    fn taken() -> *mut T {
+        ptr::addr_of!(TAKEN).cast_mut().cast()
+    }
+}
+
+impl<T, const N: usize> Queue<T, N>
+where
+    T: Send,
+{
+    pub fn new() -> Queue<T, N> {
+        let sentinel = Box::into_raw(Box::new(Node::empty()));
+
+        Queue {
+            head: unsafe { AtomicPtr::new(sentinel) }.into(),
+            tail: unsafe { AtomicPtr::new(sentinel) }.into(),
+        }
+    }
+
+    pub fn enqueue(&self, value: T, hazp: &mut HazardPointer) {
+        let value = Box::into_raw(Box::new(value));
+        loop {
+            // Safety: Will always point to at least the sentinel node
+            let tail_node = self.tail.safe_load(hazp).unwrap();
+            let tail_ptr: *const Node<T, N> = tail_node;
+
+            let idx = tail_node.enq_idx.fetch_add(1, SeqCst);
+            if idx >= N {
+                // This node is full
+                if tail_ptr != self.tail.load_ptr() {
+                    continue;
+                }
+                let next_ptr = tail_node.next.load_ptr();
+                if !next_ptr.is_null() {
+                    // Help the other enqueuer move the tail
+                    let _ = unsafe {
+                        self.tail
+                            .compare_exchange_ptr(tail_ptr.cast_mut(), next_ptr)
+                    };
+                    continue;
+                }
+
+                let new_node = Box::into_raw(Box::new(Node::new(value)));
+                match unsafe { tail_node.next.compare_exchange_ptr(null_mut(), new_node) } {
+                    Ok(_) => {
+                        let _ = unsafe {
+                            self.tail
+                                .compare_exchange_ptr(tail_ptr.cast_mut(), new_node)
+                        };
+                        return;
+                    }
+                    Err(_) => {
+                        // The node was never shared, free it but keep the value for the next try
+                        let node = unsafe { Box::from_raw(new_node) };
+                        node.items[0].store(null_mut(), SeqCst);
+                        drop(node);
+                    }
+                }
+                continue;
+            }
+
+            if tail_node.items[idx]
+                .compare_exchange(null_mut(), value, SeqCst, SeqCst)
+                .is_ok()
+            {
+                return;
+            }
+        }
+    }
+
+    pub fn dequeue(&self, hazp: &mut HazardPointer) -> Option<T> {
+        'outer: loop {
+            // Safety: Will always point to at least the sentinel node
+            let head_node = self.head.safe_load(hazp).unwrap();
+            let head_ptr: *const Node<T, N> = head_node;
+            loop {
+                let idx = head_node.deq_idx.fetch_add(1, SeqCst);
+                if idx >= N {
+                    // This node has been drained, check if there is another one
+                    let next_ptr = head_node.next.load_ptr();
+                    if next_ptr.is_null() {
+                        return None;
+                    }
+                    if let Ok(Some(old)) = unsafe {
+                        self.head
+                            .compare_exchange_ptr(head_ptr.cast_mut(), next_ptr)
+                    } {
+                        unsafe { old.retire() };
+                    }
+                    continue 'outer;
+                }
+
+                let cell = &head_node.items[idx];
+                // An enqueuer has the ticket but has not written yet, give it a moment before
+                // taking the cell away from it
+                if cell.load(SeqCst).is_null() && idx < head_node.enq_idx.load(SeqCst) {
+                    for _ in 0..4 * 1024 {
+                        if !cell.load(SeqCst).is_null() {
+                            break;
+                        }
+                        hint::spin_loop();
+                    }
+                }
+                let value = cell.swap(Self::taken(), SeqCst);
+                if !value.is_null() {
+                    // Safety: The swap handed the value to us and nobody else
+                    return Some(*unsafe { Box::from_raw(value) });
+                }
+
+                let enq_idx = head_node.enq_idx.load(SeqCst);
+                if idx + 1 >= enq_idx {
+                    if !head_node.next.load_ptr().is_null() {
+                        continue;
+                    }
+                    // Is empty, move the enqueuers past the cells burned by dequeuers
+                    let _ = head_node
+                        .enq_idx
+                        .compare_exchange(enq_idx, idx + 1, SeqCst, SeqCst);
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{sync::Arc, thread};
+
+    use haphazard::{Domain, HazardPointer};
+
+    use super::Queue;
+
+    #[test]
+    fn basic() {
+        let queue: Queue<i32, 10> = Queue::new();
+        let mut hazp = HazardPointer::new();
+        assert_eq!(queue.dequeue(&mut hazp), None);
+        for i in 0..100 {
+            queue.enqueue(i, &mut hazp);
+        }
+        for i in 0..100 {
+            assert_eq!(queue.dequeue(&mut hazp), Some(i));
+        }
+        assert_eq!(queue.dequeue(&mut hazp), None);
+    }
+
+    #[test]
+    fn reuses_cells_after_empty_dequeues() {
+        let queue: Queue<i32, 4> = Queue::new();
+        let mut hazp = HazardPointer::new();
+        // Dequeueing from an empty queue burns cells, values must still come out in order
+        for i in 0..20 {
+            assert_eq!(queue.dequeue(&mut hazp), None);
+            queue.enqueue(i, &mut hazp);
+            queue.enqueue(i + 100, &mut hazp);
+            assert_eq!(queue.dequeue(&mut hazp), Some(i));
+            assert_eq!(queue.dequeue(&mut hazp), Some(i + 100));
+        }
+    }
+
+    #[test]
+    fn basic_concurrent() {
+        let queue: Arc<Queue<usize, 16>> = Arc::new(Queue::new());
+
+        let mut producers = vec![];
+        for i in 0..4 {
+            let queue = Arc::clone(&queue);
+            let handle = thread::spawn(move || {
+                let mut hazp = HazardPointer::new();
+                for j in 0..100 {
+                    queue.enqueue(i * 100 + j, &mut hazp);
+                }
+            });
+            producers.push(handle);
+        }
+
+        let mut consumers = vec![];
+        for _ in 0..4 {
+            let queue = Arc::clone(&queue);
+            let handle = thread::spawn(move || {
+                let mut hazp = HazardPointer::new();
+                let mut sum = 0;
+                for _ in 0..100 {
+                    loop {
+                        if let Some(v) = queue.dequeue(&mut hazp) {
+                            sum += v;
+                            break;
+                        }
+                    }
+                }
+                sum
+            });
+            consumers.push(handle);
+        }
+
+        for handle in producers {
+            handle.join().unwrap();
+        }
+        let sum: usize = consumers.into_iter().map(|h| h.join().unwrap()).sum();
+        assert_eq!(sum, 400 * 399 / 2, "Sums do not match!");
+        drop(queue);
+        Domain::global().eager_reclaim();
+    }
+
+    #[test]
+    fn dropping_drops_leftover_items() {
+        let item = Arc::new(());
+        let queue: Queue<Arc<()>, 10> = Queue::new();
+        let mut hazp = HazardPointer::new();
+        for _ in 0..25 {
+            queue.enqueue(Arc::clone(&item), &mut hazp);
+        }
+        assert_eq!(Arc::strong_count(&item), 26);
+        drop(queue);
+        assert_eq!(Arc::strong_count(&item), 1);
+    }
+}
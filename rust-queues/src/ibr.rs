@@ -0,0 +1,352 @@
+use std::{
+    cell::{Cell, RefCell},
+    ptr::null_mut,
+    sync::{
+        atomic::{AtomicBool, AtomicPtr, AtomicU64, Ordering::SeqCst},
+        Mutex,
+    },
+};
+
+use crate::sync;
+
+// Interval based reclamation, the 2GE-IBR variant from "Interval-Based Memory Reclamation" by Wen
+// et al. Every allocation records the epoch it was born in and every retired one the epoch it was
+// retired in. A thread reserves the interval of epochs it has read pointers in during an
+// operation, and a retired allocation is freed once its lifetime overlaps no reservation. Unlike
+// with EBR a stalled thread only holds back allocations that were alive during its reservation
+
+// Allocations per thread between epoch increments
+const EPOCH_FREQ: usize = 128;
+// Retires per thread between scans of the reservations
+const EMPTY_FREQ: usize = 64;
+
+// The lower bound of an inactive reservation, it overlaps nothing as epochs start at 1
+const INACTIVE: u64 = u64::MAX;
+
+static EPOCH: AtomicU64 = AtomicU64::new(1);
+// Push only list of reservations, they are reused once their handle is dropped
+static RESERVATIONS: AtomicPtr<Reservation> = AtomicPtr::new(null_mut());
+// Retired allocations that were still reserved when the handle that retired them was dropped
+static ORPHANS: Mutex<Vec<Retired>> = Mutex::new(Vec::new());
+
+thread_local! {
+    static ALLOCS: Cell<usize> = const { Cell::new(0) };
+}
+
+// The value comes first so a pointer to the block is also a pointer to the value
+#[repr(C)]
+struct Block<U> {
+    value: U,
+    birth: u64,
+}
+
+struct Retired {
+    ptr: *mut u8,
+    birth: u64,
+    retire: u64,
+    drop: unsafe fn(*mut u8),
+}
+
+// A retired allocation is unreachable, so whoever frees it has exclusive access. The queues only
+// share allocations between threads when their values are Send
+unsafe impl Send for Retired {}
+
+unsafe fn drop_block<U>(ptr: *mut u8) {
+    drop(Box::from_raw(ptr.cast::<Block<U>>()));
+}
+
+struct Reservation {
+    lower: AtomicU64,
+    upper: AtomicU64,
+    in_use: AtomicBool,
+    next: AtomicPtr<Reservation>,
+}
+
+impl Reservation {
+    fn acquire() -> &'static Reservation {
+        let mut current = RESERVATIONS.load(SeqCst);
+        while !current.is_null() {
+            // Safety: Reservations are never freed
+            let reservation = unsafe { &*current };
+            if reservation
+                .in_use
+                .compare_exchange(false, true, SeqCst, SeqCst)
+                .is_ok()
+            {
+                return reservation;
+            }
+            current = reservation.next.load(SeqCst);
+        }
+
+        let reservation: &'static Reservation = Box::leak(Box::new(Reservation {
+            lower: AtomicU64::new(INACTIVE),
+            upper: AtomicU64::new(0),
+            in_use: AtomicBool::new(true),
+            next: AtomicPtr::new(null_mut()),
+        }));
+        let mut head = RESERVATIONS.load(SeqCst);
+        loop {
+            reservation.next.store(head, SeqCst);
+            match RESERVATIONS.compare_exchange(
+                head,
+                (reservation as *const Reservation).cast_mut(),
+                SeqCst,
+                SeqCst,
+            ) {
+                Ok(_) => return reservation,
+                Err(new_head) => head = new_head,
+            }
+        }
+    }
+
+    // Does the lifetime of the retired allocation overlap this reservation
+    fn conflicts(lower: u64, upper: u64, retired: &Retired) -> bool {
+        retired.birth <= upper && retired.retire >= lower
+    }
+}
+
+/// Allocates the value with its birth epoch, the pointer must be freed with [`dealloc`] or
+/// [`Guard::retire`].
+pub fn alloc<U>(value: U) -> *mut U {
+    ALLOCS.with(|allocs| {
+        allocs.set(allocs.get() + 1);
+        if allocs.get() % EPOCH_FREQ == 0 {
+            EPOCH.fetch_add(1, SeqCst);
+        }
+    });
+    let block = Box::new(Block {
+        value,
+        birth: EPOCH.load(SeqCst),
+    });
+    Box::into_raw(block).cast()
+}
+
+/// Frees a pointer that no other thread can be using.
+///
+/// # Safety
+/// The pointer must come from [`alloc`] and not have been freed or retired yet
+pub unsafe fn dealloc<U>(ptr: *mut U) {
+    drop_block::<U>(ptr.cast());
+}
+
+/// Per thread state: a reservation in the global list and the allocations this thread retired.
+pub struct Handle {
+    reservation: &'static Reservation,
+    retired: RefCell<Vec<Retired>>,
+    retires: Cell<usize>,
+}
+
+impl Default for Handle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Handle {
+    pub fn new() -> Self {
+        Handle {
+            reservation: Reservation::acquire(),
+            retired: RefCell::new(Vec::new()),
+            retires: Cell::new(0),
+        }
+    }
+
+    /// Reserves the current epoch for the duration of an operation.
+    pub fn pin(&mut self) -> Guard<'_> {
+        let epoch = EPOCH.load(SeqCst);
+        self.reservation.upper.store(epoch, SeqCst);
+        self.reservation.lower.store(epoch, SeqCst);
+        Guard { handle: self }
+    }
+
+    /// Frees every retired allocation whose lifetime overlaps no reservation, and takes over
+    /// those left behind by dropped handles.
+    pub fn reclaim(&mut self) {
+        adopt_and_scan(self.retired.get_mut());
+    }
+}
+
+impl Drop for Handle {
+    fn drop(&mut self) {
+        let retired = self.retired.get_mut();
+        scan(retired);
+        if !retired.is_empty() {
+            ORPHANS
+                .lock()
+                .expect("The IBR orphan list was poisoned")
+                .append(retired);
+        }
+        self.reservation.in_use.store(false, SeqCst);
+    }
+}
+
+fn adopt_and_scan(retired: &mut Vec<Retired>) {
+    if let Ok(mut orphans) = ORPHANS.try_lock() {
+        retired.append(&mut orphans);
+    }
+    scan(retired);
+}
+
+fn scan(retired: &mut Vec<Retired>) {
+    let mut intervals = Vec::new();
+    let mut current = RESERVATIONS.load(SeqCst);
+    while !current.is_null() {
+        // Safety: Reservations are never freed
+        let reservation = unsafe { &*current };
+        let lower = reservation.lower.load(SeqCst);
+        if lower != INACTIVE {
+            intervals.push((lower, reservation.upper.load(SeqCst)));
+        }
+        current = reservation.next.load(SeqCst);
+    }
+
+    retired.retain(|r| {
+        if intervals
+            .iter()
+            .any(|&(lower, upper)| Reservation::conflicts(lower, upper, r))
+        {
+            return true;
+        }
+        // Safety: Retired allocations are unreachable, and no thread that could still have read
+        // the pointer has a reservation left that covers it
+        unsafe { (r.drop)(r.ptr) };
+        false
+    });
+}
+
+/// An operation in progress, pointers read through [`Guard::protect`] stay valid until it is
+/// dropped.
+pub struct Guard<'h> {
+    handle: &'h mut Handle,
+}
+
+impl Guard<'_> {
+    /// Loads the pointer and extends the reservation up to the current epoch, so the pointee can
+    /// not be freed while the guard lives.
+    pub fn protect<U>(&self, atomic: &sync::atomic::AtomicPtr<U>) -> *mut U {
+        let reservation = self.handle.reservation;
+        let mut upper = reservation.upper.load(SeqCst);
+        loop {
+            let ptr = atomic.load(SeqCst);
+            let epoch = EPOCH.load(SeqCst);
+            if epoch == upper {
+                return ptr;
+            }
+            reservation.upper.store(epoch, SeqCst);
+            upper = epoch;
+        }
+    }
+
+    /// Frees the pointer once no reservation covers its lifetime anymore.
+    ///
+    /// # Safety
+    /// The pointer must come from [`alloc`], be unreachable for threads that start an operation
+    /// from now on, and not have been retired before
+    pub unsafe fn retire<U>(&self, ptr: *mut U) {
+        self.retire_with(ptr, drop_block::<U>);
+    }
+
+    /// Like [`Guard::retire`], but calls `free` with the pointer instead of freeing it. The
+    /// allocation can then be reused, it keeps its old birth epoch which only makes its lifetime
+    /// look longer.
+    ///
+    /// # Safety
+    /// Same as for [`Guard::retire`], and `free` must take over the allocation as a `*mut U`
+    pub unsafe fn retire_with<U>(&self, ptr: *mut U, free: unsafe fn(*mut u8)) {
+        let birth = (*ptr.cast::<Block<U>>()).birth;
+        let mut retired = self.handle.retired.borrow_mut();
+        retired.push(Retired {
+            ptr: ptr.cast(),
+            birth,
+            retire: EPOCH.load(SeqCst),
+            drop: free,
+        });
+        let retires = self.handle.retires.get() + 1;
+        self.handle.retires.set(retires);
+        if retires % EMPTY_FREQ == 0 {
+            adopt_and_scan(&mut retired);
+        }
+    }
+}
+
+impl Drop for Guard<'_> {
+    fn drop(&mut self) {
+        let reservation = self.handle.reservation;
+        reservation.lower.store(INACTIVE, SeqCst);
+        reservation.upper.store(0, SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{atomic::Ordering::SeqCst, Arc};
+
+    use super::{alloc, Handle};
+    use crate::sync::atomic::AtomicPtr;
+
+    // Other tests may briefly hold reservations that cover the retired pointer
+    fn reclaim_until_dropped(handle: &mut Handle, item: &Arc<()>) {
+        for _ in 0..10_000 {
+            handle.reclaim();
+            if Arc::strong_count(item) == 1 {
+                return;
+            }
+            std::thread::yield_now();
+        }
+        panic!("A retired pointer was never freed");
+    }
+
+    #[test]
+    fn retired_pointers_are_freed() {
+        let item = Arc::new(());
+        let mut handle = Handle::new();
+        for _ in 0..100 {
+            let ptr = alloc(Arc::clone(&item));
+            let guard = handle.pin();
+            unsafe { guard.retire(ptr) };
+        }
+        reclaim_until_dropped(&mut handle, &item);
+    }
+
+    #[test]
+    fn reservation_keeps_pointer_alive() {
+        let item = Arc::new(());
+        let atomic = AtomicPtr::new(alloc(Arc::clone(&item)));
+
+        let mut reader = Handle::new();
+        let guard = reader.pin();
+        let ptr = guard.protect(&atomic);
+
+        let mut writer = Handle::new();
+        let old = atomic.swap(alloc(Arc::clone(&item)), SeqCst);
+        assert_eq!(old, ptr);
+        unsafe { writer.pin().retire(old) };
+        writer.reclaim();
+        assert_eq!(Arc::strong_count(&item), 3);
+        // Safety: Still protected by the reader's reservation
+        assert!(Arc::ptr_eq(unsafe { &*ptr }, &item));
+
+        drop(guard);
+        let guard = writer.pin();
+        unsafe { guard.retire(atomic.swap(std::ptr::null_mut(), SeqCst)) };
+        drop(guard);
+        reclaim_until_dropped(&mut writer, &item);
+    }
+
+    #[test]
+    fn dropped_handles_leave_orphans() {
+        let item = Arc::new(());
+        // Allocated before the reader pins, so its lifetime is covered by the reservation
+        let ptr = alloc(Arc::clone(&item));
+        let mut reader = Handle::new();
+        let guard = reader.pin();
+        {
+            let mut writer = Handle::new();
+            unsafe { writer.pin().retire(ptr) };
+        }
+        // The reader's reservation covered the pointer when the writer was dropped
+        assert_eq!(Arc::strong_count(&item), 2);
+        drop(guard);
+        reclaim_until_dropped(&mut reader, &item);
+    }
+}
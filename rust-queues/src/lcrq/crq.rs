@@ -0,0 +1,333 @@
+use crossbeam_utils::CachePadded;
+use std::{
+    arch::asm,
+    array,
+    fmt::Debug,
+    ptr::{self, null_mut},
+    sync::atomic::{AtomicPtr, AtomicUsize, Ordering},
+};
+
+use crate::shared_queue::{TryDequeueError, TryEnqueueError};
+
+use haphazard;
+
+// Top bit of the tail, set once the ring is closed
+const CLOSED_BIT: usize = 1 << 63;
+
+// Make sure cells are on different cache lines. The value and index are read and written together
+// with a double width CAS, so the value has to come first and the cell must be 16 byte aligned
+#[repr(C, align(128))]
+struct Cell<T> {
+    // NOTE: This is a std::sync AtomicPtr and not the haphazard one
+    value: AtomicPtr<T>,
+    unsafe_and_index: AtomicUsize,
+}
+
+impl<T> Debug for Cell<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (unsafe_bit, index) = Self::split(self.unsafe_and_index.load(Ordering::Relaxed));
+        f.debug_struct("Cell")
+            .field("unsafe", &unsafe_bit)
+            .field("index", &index)
+            .field("value", &self.value)
+            .finish()
+    }
+}
+
+impl<T> Cell<T> {
+    const UNSAFE_BIT_MASK: usize = 1 << 63;
+    const INDEX_MASK: usize = !Cell::<T>::UNSAFE_BIT_MASK;
+
+    fn new(index: usize) -> Self {
+        Self {
+            value: AtomicPtr::new(null_mut()),
+            unsafe_and_index: AtomicUsize::new(index),
+        }
+    }
+
+    #[inline]
+    fn split(raw: usize) -> (bool, usize) {
+        ((raw & Self::UNSAFE_BIT_MASK) != 0, raw & Self::INDEX_MASK)
+    }
+
+    // Atomically replaces both the value and the unsafe bit and index if they are both unchanged,
+    // the equivalent of CAS2 in the reference implementations
+    fn compare_exchange2(&self, current: (*mut T, usize), new: (*mut T, usize)) -> bool {
+        let dst: *const Self = self;
+        let success: u8;
+        // Safety: The cell is 16 byte aligned with the value in the low and the index in the high
+        // quadword. rbx can not be named as an operand, so the low half of the new value is
+        // swapped in and out of it around the cmpxchg16b. The address is pinned to rdi, as the
+        // compiler may otherwise pick rbx for it
+        unsafe {
+            asm!(
+                "xchg {new_lo}, rbx",
+                "lock cmpxchg16b xmmword ptr [rdi]",
+                "mov rbx, {new_lo}",
+                "setz {success}",
+                new_lo = inout(reg) new.0 as usize => _,
+                success = out(reg_byte) success,
+                inout("rax") current.0.addr() => _,
+                inout("rdx") current.1 => _,
+                in("rcx") new.1,
+                in("rdi") dst,
+                options(nostack),
+            );
+        }
+        success != 0
+    }
+}
+
+#[derive(Debug)]
+#[allow(clippy::upper_case_acronyms)] // Named as in the paper
+pub struct CRQ<T, const N: usize> {
+    head: CachePadded<AtomicUsize>,
+    tail: CachePadded<AtomicUsize>, // Top bit here is set if the queue is closed
+    array: [Cell<T>; N],
+    pub next: CachePadded<haphazard::AtomicPtr<CRQ<T, N>>>,
+}
+
+impl<T, const N: usize> CRQ<T, N> {
+    pub fn new() -> Self {
+        CRQ {
+            head: AtomicUsize::new(0).into(),
+            tail: AtomicUsize::new(0).into(),
+            array: array::from_fn(Cell::new),
+            next: unsafe { haphazard::AtomicPtr::new(null_mut()).into() },
+        }
+    }
+
+    pub fn new_with_item(value_ptr: *const T) -> Self {
+        let crq = CRQ::new();
+        crq.enqueue(value_ptr)
+            .expect("Failed to enqueue an item in a new and empty CRQ, Should not happen ever");
+        crq
+    }
+
+    // Returns Ok() if enqueue was succesfull, Err() with the value if the queue is full or closed
+    pub fn enqueue(&self, value_ptr: *const T) -> Result<(), TryEnqueueError<*const T>> {
+        let mut close_tries = 0;
+        loop {
+            let tail_ticket = self.tail.fetch_add(1, Ordering::SeqCst);
+            if tail_ticket & CLOSED_BIT != 0 {
+                return Err(TryEnqueueError::Closed(value_ptr));
+            }
+
+            let cell = &self.array[tail_ticket % N];
+            let raw = cell.unsafe_and_index.load(Ordering::SeqCst);
+            let (unsafe_bit, index) = Cell::<T>::split(raw);
+            if cell.value.load(Ordering::SeqCst).is_null()
+                && index <= tail_ticket
+                && (!unsafe_bit || self.head.load(Ordering::SeqCst) < tail_ticket)
+                && cell
+                    .compare_exchange2((ptr::null_mut(), raw), (value_ptr.cast_mut(), tail_ticket))
+            {
+                return Ok(());
+            }
+
+            // Check if the queue is full
+            if tail_ticket >= self.head.load(Ordering::SeqCst) + N {
+                close_tries += 1;
+                if self.close(tail_ticket, close_tries > 10) {
+                    return Err(TryEnqueueError::Full(value_ptr));
+                }
+            }
+        }
+    }
+
+    pub fn dequeue(&self) -> Result<*mut T, TryDequeueError> {
+        loop {
+            let head_ticket = self.head.fetch_add(1, Ordering::SeqCst);
+            let cell = &self.array[head_ticket % N];
+
+            let mut r: usize = 0;
+            let mut tail_ticket = 0;
+            loop {
+                let raw = cell.unsafe_and_index.load(Ordering::SeqCst);
+                // Kept as a mask so it can be carried over into the new index
+                let unsafe_bit = raw & Cell::<T>::UNSAFE_BIT_MASK;
+                let index = raw & Cell::<T>::INDEX_MASK;
+                let value = cell.value.load(Ordering::SeqCst);
+
+                // An enqueuer of a later round has already used the cell
+                if index > head_ticket {
+                    break;
+                }
+
+                if !value.is_null() {
+                    if index == head_ticket {
+                        if cell.compare_exchange2(
+                            (value, raw),
+                            (ptr::null_mut(), unsafe_bit | (head_ticket + N)),
+                        ) {
+                            return Ok(value);
+                        }
+                    } else if cell
+                        .compare_exchange2((value, raw), (value, raw | Cell::<T>::UNSAFE_BIT_MASK))
+                    {
+                        // The value belongs to an earlier round whose dequeuer has not arrived
+                        // yet, mark the cell unsafe so no enqueuer reuses it until it is taken
+                        break;
+                    }
+                } else {
+                    if (r % 256) == 0 {
+                        tail_ticket = self.tail.load(Ordering::SeqCst);
+                    }
+                    let closed = tail_ticket & CLOSED_BIT != 0;
+                    let tail = tail_ticket & !CLOSED_BIT;
+
+                    if unsafe_bit != 0 || tail < head_ticket + 1 || closed || r > 4 * 1024 {
+                        // Move the cell past our round so no enqueuer can use it for this ticket
+                        if cell.compare_exchange2(
+                            (value, raw),
+                            (value, unsafe_bit | (head_ticket + N)),
+                        ) {
+                            break;
+                        }
+                    }
+                    r += 1;
+                }
+            }
+
+            // Is the queue empty?
+            let tail_ticket = self.tail.load(Ordering::SeqCst);
+            if (tail_ticket & !CLOSED_BIT) <= head_ticket + 1 {
+                self.fix_state();
+                if tail_ticket & CLOSED_BIT != 0 {
+                    return Err(TryDequeueError::Closed);
+                }
+                return Err(TryDequeueError::Empty);
+            }
+        }
+    }
+
+    // Closes the queue if no other enqueuer has taken a ticket since ours, or unconditionally if
+    // forced. Returns true if this call closed it
+    fn close(&self, tail_ticket: usize, force: bool) -> bool {
+        if force {
+            self.tail.fetch_or(CLOSED_BIT, Ordering::SeqCst) & CLOSED_BIT == 0
+        } else {
+            self.tail
+                .compare_exchange(
+                    tail_ticket + 1,
+                    (tail_ticket + 1) | CLOSED_BIT,
+                    Ordering::SeqCst,
+                    Ordering::SeqCst,
+                )
+                .is_ok()
+        }
+    }
+
+    #[allow(clippy::redundant_pattern_matching)]
+    fn fix_state(&self) {
+        loop {
+            let tail_ticket = self.tail.load(Ordering::SeqCst);
+            let head = self.head.load(Ordering::SeqCst);
+            if tail_ticket != self.tail.load(Ordering::SeqCst) {
+                continue;
+            }
+            // Never true for a closed queue, fixing it must not clear the closed bit
+            if head > tail_ticket {
+                if let Ok(_) = self.tail.compare_exchange(
+                    tail_ticket,
+                    head,
+                    Ordering::SeqCst,
+                    Ordering::SeqCst,
+                ) {
+                    break;
+                }
+                continue;
+            }
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Cell, CRQ};
+    use crate::shared_queue::{TryDequeueError, TryEnqueueError};
+    use std::{
+        ptr,
+        sync::{atomic::Ordering, Arc},
+        thread,
+    };
+
+    #[test]
+    fn cell_compare_exchange2() {
+        let cell: Cell<i32> = Cell::new(3);
+        let mut value = 7;
+        let value_ptr: *mut i32 = &mut value;
+        assert!(!cell.compare_exchange2((ptr::null_mut(), 2), (value_ptr, 5)));
+        assert!(cell.compare_exchange2((ptr::null_mut(), 3), (value_ptr, 5)));
+        assert_eq!(cell.value.load(Ordering::Relaxed), value_ptr);
+        assert_eq!(cell.unsafe_and_index.load(Ordering::Relaxed), 5);
+    }
+
+    #[test]
+    fn basic_crq() {
+        let crq: CRQ<i32, 5> = CRQ::new();
+        for i in 0..5 {
+            let item = Box::into_raw(Box::new(i));
+            assert_eq!(crq.enqueue(item), Ok(()));
+        }
+        // CRQ is now full, should fail
+        let item = Box::into_raw(Box::new(5));
+        assert_eq!(
+            crq.enqueue(item),
+            Err(TryEnqueueError::Full(item.cast_const()))
+        );
+        let _ = unsafe { Box::from_raw(item) };
+
+        for i in 0..5 {
+            let value = unsafe { Box::from_raw(crq.dequeue().unwrap()) };
+            assert_eq!(value, Box::new(i));
+        }
+        assert_eq!(crq.dequeue(), Err(TryDequeueError::Closed));
+    }
+
+    #[test]
+    fn crq_reuses_cells() {
+        let crq: CRQ<i32, 4> = CRQ::new();
+        // Dequeueing from an empty ring advances the cells, they must still be usable afterwards
+        for i in 0..20 {
+            assert_eq!(crq.dequeue(), Err(TryDequeueError::Empty));
+            let item = Box::into_raw(Box::new(i));
+            assert_eq!(crq.enqueue(item), Ok(()));
+            let value = unsafe { Box::from_raw(crq.dequeue().unwrap()) };
+            assert_eq!(value, Box::new(i));
+        }
+    }
+
+    #[test]
+    fn crq_concurrent() {
+        const N: usize = 10;
+        let crq: Arc<CRQ<usize, N>> = Arc::new(CRQ::new());
+
+        let mut handles = vec![];
+
+        for i in 0..N {
+            let queue = Arc::clone(&crq);
+            let handle = thread::spawn(move || {
+                let v = Box::into_raw(Box::new(i));
+                queue.enqueue(v).is_ok()
+            });
+            handles.push(handle);
+        }
+
+        for handle in handles {
+            assert!(handle.join().unwrap());
+        }
+
+        let mut dequeue_sum = 0;
+        while let Ok(ptr) = crq.dequeue() {
+            let value = unsafe { Box::from_raw(ptr) };
+            dequeue_sum += *value;
+        }
+
+        // Sum of first n natural numbers (0 to n-1)
+        let expected_sum = N * (N - 1) / 2;
+
+        assert_eq!(expected_sum, dequeue_sum, "Sums do not match!");
+    }
+}
@@ -0,0 +1,239 @@
+use std::{ptr, sync::Arc};
+
+use haphazard::{AtomicPtr, HazardPointer};
+
+use crossbeam_utils::CachePadded;
+
+use crate::shared_queue::{SharedQueue, TryDequeueError};
+
+use super::crq::CRQ;
+
+pub struct SharedLCRQ<'a, T, const N: usize> {
+    queue: Arc<LCRQ<T, N>>,
+    hazard1: HazardPointer<'a>,
+    hazard2: HazardPointer<'a>,
+}
+
+impl<'a, T, const N: usize> SharedLCRQ<'a, T, N> {
+    pub fn try_dequeue(&mut self) -> Result<T, TryDequeueError> {
+        self.queue.dequeue(&mut self.hazard1, &mut self.hazard2)
+    }
+}
+
+impl<'a, T, const N: usize> SharedQueue<T> for SharedLCRQ<'a, T, N> {
+    fn new() -> Self {
+        Self {
+            queue: Arc::new(LCRQ::new()),
+            hazard1: HazardPointer::new(),
+            hazard2: HazardPointer::new(),
+        }
+    }
+
+    fn enqueue(&mut self, val: T) {
+        self.queue.enqueue(val, &mut self.hazard1);
+    }
+
+    fn dequeue(&mut self) -> Option<T> {
+        self.try_dequeue().ok()
+    }
+}
+
+impl<'a, T, const N: usize> Clone for SharedLCRQ<'a, T, N> {
+    fn clone(&self) -> Self {
+        Self {
+            queue: self.queue.clone(),
+            hazard1: HazardPointer::new(),
+            hazard2: HazardPointer::new(),
+        }
+    }
+}
+
+#[allow(clippy::upper_case_acronyms)] // Named as in the paper
+pub(crate) struct LCRQ<T, const N: usize> {
+    head: CachePadded<AtomicPtr<CRQ<T, N>>>,
+    tail: CachePadded<AtomicPtr<CRQ<T, N>>>,
+}
+
+// The queue owns the boxed values stored in its segments, so it can only be shared between threads
+// if the values themselves can be sent between them
+unsafe impl<T: Send, const N: usize> Send for LCRQ<T, N> {}
+unsafe impl<T: Send, const N: usize> Sync for LCRQ<T, N> {}
+
+impl<T, const N: usize> Drop for LCRQ<T, N> {
+    fn drop(&mut self) {
+        // Empty the queue to drop any leftover items
+        let mut hazard1 = HazardPointer::new();
+        let mut hazard2 = HazardPointer::new();
+        while self.dequeue(&mut hazard1, &mut hazard2).is_ok() {}
+
+        let head = self.head.load_ptr();
+        let tail = self.tail.load_ptr();
+        if head == tail {
+            let old = unsafe { self.head.swap_ptr(ptr::null_mut()) }.expect("A LCRQ with both head and tail as null was dropped. This should never happen and indicates a bug or memory corruption");
+            unsafe { old.retire() };
+        } else {
+            panic!("Drop for LCRQ somehow failed to dequeue all its items")
+        }
+    }
+}
+
+impl<T, const N: usize> LCRQ<T, N> {
+    pub(crate) fn new() -> Self {
+        let initial: *mut CRQ<T, N> = Box::into_raw(Box::new(CRQ::new()));
+        Self {
+            head: unsafe { AtomicPtr::new(initial) }.into(),
+            tail: unsafe { AtomicPtr::new(initial) }.into(),
+        }
+    }
+    pub(crate) fn enqueue(&self, val: T, hazard: &mut HazardPointer) {
+        let val: *const T = Box::into_raw(Box::new(val));
+        loop {
+            // fast path: Add item to current CRQ
+            let queue = self.tail.safe_load(hazard).unwrap();
+            let queue_ptr: *const CRQ<T, N> = queue;
+            if queue.enqueue(val).is_ok() {
+                return;
+            }
+            // Slow path: Tail is full, allocate and add a new crq
+            let new_tail: AtomicPtr<CRQ<T, N>> = AtomicPtr::from(Box::new(CRQ::new_with_item(val)));
+            let new_tail_ptr = new_tail.load_ptr();
+            match unsafe {
+                queue
+                    .next
+                    .compare_exchange_ptr(ptr::null_mut(), new_tail_ptr)
+            } {
+                Ok(_) => {
+                    // Next successfully inserted, update tail to point to that
+                    let _ = unsafe {
+                        self.tail
+                            .compare_exchange_ptr(queue_ptr.cast_mut(), new_tail_ptr)
+                    };
+                    return;
+                }
+                Err(next) => {
+                    let _ = unsafe { self.tail.compare_exchange_ptr(queue_ptr.cast_mut(), next) };
+                    // Drop the failed new tail so it does not leak, the value stays ours
+                    let _ = unsafe { new_tail.retire() };
+                }
+            }
+        }
+    }
+    pub(crate) fn dequeue(
+        &self,
+        hazard1: &mut HazardPointer,
+        hazard2: &mut HazardPointer,
+    ) -> Result<T, TryDequeueError> {
+        loop {
+            let queue = self.head.safe_load(hazard1).unwrap();
+            if let Ok(v) = queue.dequeue() {
+                return Ok(*unsafe { Box::from_raw(v) });
+            }
+            // Failed, is this queue empty?
+            let Some(next_ptr) = hazard2.protect_ptr(unsafe { queue.next.as_std() }) else {
+                return Err(TryDequeueError::Empty);
+            };
+            // LCRQ is not empty, try to dequeue again
+            if let Ok(v) = queue.dequeue() {
+                return Ok(*unsafe { Box::from_raw(v) });
+            }
+            // CRQ is empty, update head and restart
+            let queue_ptr: *const CRQ<T, N> = queue;
+            if let Ok(Some(old)) = unsafe {
+                self.head
+                    .compare_exchange_ptr(queue_ptr.cast_mut(), next_ptr.0.as_ptr())
+            } {
+                // The old CRQ is now empty, so we retire it
+                unsafe { old.retire() };
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{sync::Arc, thread};
+
+    use haphazard::{Domain, HazardPointer};
+
+    use super::LCRQ;
+    use crate::shared_queue::TryDequeueError;
+
+    #[test]
+    fn basic() {
+        let queue: LCRQ<i32, 10> = LCRQ::new();
+        let mut hazard = HazardPointer::new();
+        for i in 0..100 {
+            queue.enqueue(i, &mut hazard);
+        }
+        let mut hazard2 = HazardPointer::new();
+        for i in 0..100 {
+            let v = queue.dequeue(&mut hazard, &mut hazard2).unwrap();
+            assert_eq!(v, i);
+        }
+        assert_eq!(
+            queue.dequeue(&mut hazard, &mut hazard2),
+            Err(TryDequeueError::Empty)
+        );
+    }
+
+    #[test]
+    fn basic_concurrent() {
+        let queue: Arc<LCRQ<usize, 10>> = Arc::new(LCRQ::new());
+
+        let mut handles = vec![];
+
+        for i in 0..4 {
+            let queue = Arc::clone(&queue);
+            let handle = thread::spawn(move || {
+                let mut hazard = HazardPointer::new();
+                for j in 0..100 {
+                    queue.enqueue(i * 100 + j, &mut hazard);
+                }
+            });
+            handles.push(handle);
+        }
+
+        let mut consumers = vec![];
+        for _ in 0..4 {
+            let queue = Arc::clone(&queue);
+            let handle = thread::spawn(move || {
+                let mut hazard1 = HazardPointer::new();
+                let mut hazard2 = HazardPointer::new();
+                let mut sum = 0;
+                for _ in 0..100 {
+                    loop {
+                        if let Ok(v) = queue.dequeue(&mut hazard1, &mut hazard2) {
+                            sum += v;
+                            break;
+                        }
+                    }
+                }
+                sum
+            });
+            consumers.push(handle);
+        }
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        let sum: usize = consumers.into_iter().map(|h| h.join().unwrap()).sum();
+        assert_eq!(sum, 400 * 399 / 2, "Sums do not match!");
+        drop(queue);
+        Domain::global().eager_reclaim();
+    }
+
+    #[test]
+    fn dropping_drops_leftover_items() {
+        let item = Arc::new(());
+        let queue: LCRQ<Arc<()>, 10> = LCRQ::new();
+        let mut hazard = HazardPointer::new();
+        for _ in 0..25 {
+            queue.enqueue(Arc::clone(&item), &mut hazard);
+        }
+        drop(hazard);
+        assert_eq!(Arc::strong_count(&item), 26);
+        drop(queue);
+        Domain::global().eager_reclaim();
+        assert_eq!(Arc::strong_count(&item), 1);
+    }
+}
@@ -0,0 +1,3 @@
+mod crq;
+#[allow(clippy::module_inception)]
+pub mod lcrq;
@@ -1,15 +1,25 @@
-#![feature(strict_provenance, thread_id_value)]
-#![warn(fuzzy_provenance_casts)]
-
 //#[cfg(not(miri))]
 //#[global_allocator]
 //static GLOBAL: tikv_jemallocator::Jemalloc = tikv_jemallocator::Jemalloc;
 
 pub mod ms_queue;
 
+pub mod async_queue;
+pub mod batch_benchmark;
 pub mod benchmark_utils;
+pub mod blocking_queue;
+pub mod ccqueue;
+pub mod core_utils;
+pub mod faa_array_queue;
+pub mod ibr;
+// LCRQ relies on cmpxchg16b
+#[cfg(target_arch = "x86_64")]
+pub mod lcrq;
 pub mod lprq;
+pub mod lscq;
 pub mod mpmc_benchmark;
 pub mod pairwise_benchmark;
+pub mod reclaim;
 pub mod shared_queue;
-pub mod core_utils;
+mod sync;
+pub mod wfqueue;
@@ -0,0 +1,4 @@
+use crate::reclaim::Aarc;
+
+// LPRQ with its segments reclaimed by atomic Arcs from aarc
+pub type SharedLPRQ<T> = super::lprq::SharedLPRQ<T, Aarc>;
@@ -0,0 +1,222 @@
+use std::{hint, sync::Arc};
+
+use crate::shared_queue::{SharedQueue, TryDequeueError, TryEnqueueError};
+
+use super::prq::PRQ;
+
+/// A bounded MPMC queue holding at most `N` items, built on a single PRQ ring. `N` must be a
+/// power of two.
+///
+/// Unlike the PRQ segments inside LPRQ, a full ring is not abandoned. The enqueue fails with
+/// [`TryEnqueueError::Full`] and the ring is reopened once consumers have drained it.
+///
+/// An enqueue that finds the ring full closes it, and it stays closed until it is completely empty
+/// again. Dequeueing a single item then does not make room for another one, every enqueue keeps
+/// failing until consumers have taken out all items and a dequeue has found the queue empty.
+/// [`SharedQueue::enqueue`] spins for that long.
+pub struct BoundedPRQ<T: 'static, const N: usize> {
+    queue: Arc<Bounded<T, N>>,
+}
+
+impl<T: 'static, const N: usize> BoundedPRQ<T, N> {
+    /// Enqueues a value, or hands it back in [`TryEnqueueError::Full`] if there is no room.
+    ///
+    /// Once this has returned `Full`, it keeps failing until the queue has been drained
+    /// completely and a dequeue has found it empty, even if there are fewer than `N` items left.
+    pub fn try_enqueue(&self, val: T) -> Result<(), TryEnqueueError<T>> {
+        self.queue.try_enqueue(val)
+    }
+
+    pub fn try_dequeue(&self) -> Result<T, TryDequeueError> {
+        self.queue.try_dequeue()
+    }
+
+    // Approximate number of items in the queue
+    pub fn len(&self) -> usize {
+        self.queue.prq.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.prq.is_empty()
+    }
+}
+
+impl<T: 'static, const N: usize> SharedQueue<T> for BoundedPRQ<T, N> {
+    fn new() -> Self {
+        Self {
+            queue: Arc::new(Bounded::new()),
+        }
+    }
+
+    // Spins until there is room in the queue
+    fn enqueue(&mut self, mut val: T) {
+        while let Err(e) = self.queue.try_enqueue(val) {
+            val = e.into_inner();
+            hint::spin_loop();
+        }
+    }
+
+    fn dequeue(&mut self) -> Option<T> {
+        self.queue.try_dequeue().ok()
+    }
+}
+
+impl<T: 'static, const N: usize> Clone for BoundedPRQ<T, N> {
+    fn clone(&self) -> Self {
+        Self {
+            queue: self.queue.clone(),
+        }
+    }
+}
+
+struct Bounded<T: 'static, const N: usize> {
+    prq: PRQ<T>,
+}
+
+// The queue owns the boxed values stored in the ring, so it can only be shared between threads
+// if the values themselves can be sent between them
+unsafe impl<T: Send + 'static, const N: usize> Send for Bounded<T, N> {}
+unsafe impl<T: Send + 'static, const N: usize> Sync for Bounded<T, N> {}
+
+impl<T: 'static, const N: usize> Drop for Bounded<T, N> {
+    fn drop(&mut self) {
+        // Empty the queue to drop any leftover items
+        while self.try_dequeue().is_ok() {}
+    }
+}
+
+impl<T: 'static, const N: usize> Bounded<T, N> {
+    fn new() -> Self {
+        // The ring size is only checked at runtime by PRQ::new, reject a bad N at compile time
+        const { assert!(N.is_power_of_two(), "N must be a power of two") };
+        Self { prq: PRQ::new(N) }
+    }
+
+    fn try_enqueue(&self, val: T) -> Result<(), TryEnqueueError<T>> {
+        // The ring closes itself when it fills up, and can only be used again once drained.
+        // Check before enqueueing so attempts on a closed ring do not burn any tickets
+        if !self.prq.reopen() {
+            return Err(TryEnqueueError::Full(val));
+        }
+        let val: *mut T = Box::into_raw(Box::new(val));
+        match self.prq.enqueue(val) {
+            Ok(_) => Ok(()),
+            Err(_) => Err(TryEnqueueError::Full(*unsafe { Box::from_raw(val) })),
+        }
+    }
+
+    fn try_dequeue(&self) -> Result<T, TryDequeueError> {
+        // A closed ring is only waiting to be reopened, so to users it is just empty
+        self.prq
+            .dequeue()
+            .map(|v| *unsafe { Box::from_raw(v) })
+            .map_err(|_| TryDequeueError::Empty)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{sync::Arc, thread};
+
+    use super::BoundedPRQ;
+    use crate::shared_queue::{SharedQueue, TryDequeueError, TryEnqueueError};
+
+    #[test]
+    fn full_returns_value() {
+        let queue: BoundedPRQ<i32, 4> = BoundedPRQ::new();
+        for i in 0..4 {
+            assert_eq!(queue.try_enqueue(i), Ok(()));
+        }
+        assert_eq!(queue.len(), 4);
+        assert_eq!(queue.try_enqueue(4), Err(TryEnqueueError::Full(4)));
+        for i in 0..4 {
+            assert_eq!(queue.try_dequeue(), Ok(i));
+        }
+        assert_eq!(queue.try_dequeue(), Err(TryDequeueError::Empty));
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn reopens_after_draining() {
+        let queue: BoundedPRQ<i32, 4> = BoundedPRQ::new();
+        for round in 0..3 {
+            for i in 0..4 {
+                assert_eq!(queue.try_enqueue(round * 4 + i), Ok(()));
+            }
+            assert_eq!(queue.try_enqueue(-1), Err(TryEnqueueError::Full(-1)));
+            assert_eq!(queue.try_enqueue(-1), Err(TryEnqueueError::Full(-1)));
+            for i in 0..4 {
+                assert_eq!(queue.try_dequeue(), Ok(round * 4 + i));
+            }
+            assert_eq!(queue.try_dequeue(), Err(TryDequeueError::Empty));
+        }
+    }
+
+    #[test]
+    fn stays_full_until_drained() {
+        let queue: BoundedPRQ<i32, 4> = BoundedPRQ::new();
+        for i in 0..4 {
+            assert_eq!(queue.try_enqueue(i), Ok(()));
+        }
+        // The ring is not closed until an enqueue finds it full, so the free cell can be used
+        assert_eq!(queue.try_dequeue(), Ok(0));
+        assert_eq!(queue.try_enqueue(4), Ok(()));
+        assert_eq!(queue.try_enqueue(5), Err(TryEnqueueError::Full(5)));
+        // Now there is room for one item again, but the ring only reopens once it is empty
+        assert_eq!(queue.try_dequeue(), Ok(1));
+        assert_eq!(queue.try_enqueue(5), Err(TryEnqueueError::Full(5)));
+        for i in 2..5 {
+            assert_eq!(queue.try_dequeue(), Ok(i));
+        }
+        assert_eq!(queue.try_dequeue(), Err(TryDequeueError::Empty));
+        assert_eq!(queue.try_enqueue(5), Ok(()));
+        assert_eq!(queue.try_dequeue(), Ok(5));
+    }
+
+    #[test]
+    fn bounded_concurrent() {
+        const N: usize = 8;
+        let queue: BoundedPRQ<usize, N> = BoundedPRQ::new();
+
+        let mut handles = vec![];
+
+        for i in 0..4 {
+            let mut queue = queue.clone();
+            let handle = thread::spawn(move || {
+                for j in 0..100 {
+                    queue.enqueue(i * 100 + j);
+                }
+            });
+            handles.push(handle);
+        }
+
+        let mut dequeue_sum = 0;
+        let mut dequeued = 0;
+        let mut consumer = queue.clone();
+        while dequeued < 400 {
+            if let Some(v) = consumer.dequeue() {
+                dequeue_sum += v;
+                dequeued += 1;
+            }
+        }
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(dequeue_sum, 400 * 399 / 2, "Sums do not match!");
+        assert_eq!(consumer.dequeue(), None);
+    }
+
+    #[test]
+    fn dropping_drops_leftover_items() {
+        let item = Arc::new(());
+        let queue: BoundedPRQ<Arc<()>, 8> = BoundedPRQ::new();
+        for _ in 0..5 {
+            queue.try_enqueue(Arc::clone(&item)).unwrap();
+        }
+        assert_eq!(Arc::strong_count(&item), 6);
+        drop(queue);
+        assert_eq!(Arc::strong_count(&item), 1);
+    }
+}
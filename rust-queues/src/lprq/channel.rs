@@ -0,0 +1,353 @@
+use std::{
+    error::Error,
+    fmt, hint,
+    sync::{
+        atomic::{fence, AtomicUsize, Ordering::SeqCst},
+        Arc, Condvar, Mutex,
+    },
+};
+
+use crate::{
+    reclaim::{HazardPointers, Reclaimer},
+    shared_queue::TryDequeueError,
+};
+
+use super::lprq::{DEFAULT_RING_SIZE, LPRQ};
+
+/// Number of empty polls in recv before the receiver goes to sleep
+const SPIN_LIMIT: usize = 64;
+
+/// Creates an unbounded multi producer multi consumer channel backed by a hazard pointer LPRQ.
+///
+/// Both halves can be cloned. Once all senders are dropped the receivers drain what is left and
+/// then get [`TryRecvError::Disconnected`], once all receivers are dropped sends fail.
+pub fn channel<T: 'static>() -> (Sender<T>, Receiver<T>) {
+    channel_with_ring_size(DEFAULT_RING_SIZE)
+}
+
+/// Same as [`channel`], with the ring size of the LPRQ segments set explicitly. It must be a power
+/// of two
+pub fn channel_with_ring_size<T: 'static>(ring_size: usize) -> (Sender<T>, Receiver<T>) {
+    let chan = Arc::new(Chan {
+        queue: LPRQ::new(ring_size),
+        senders: AtomicUsize::new(1),
+        receivers: AtomicUsize::new(1),
+        sleepers: AtomicUsize::new(0),
+        lock: Mutex::new(()),
+        condvar: Condvar::new(),
+    });
+    (
+        Sender {
+            chan: chan.clone(),
+            handle: HazardPointers::handle(),
+        },
+        Receiver {
+            chan,
+            handle: HazardPointers::handle(),
+        },
+    )
+}
+
+struct Chan<T: 'static> {
+    queue: LPRQ<T>,
+    senders: AtomicUsize,
+    receivers: AtomicUsize,
+    // Receivers parked in recv, they wait on the condvar while holding the lock
+    sleepers: AtomicUsize,
+    lock: Mutex<()>,
+    condvar: Condvar,
+}
+
+impl<T: 'static> Chan<T> {
+    // Wakes receivers parked in recv, must be called after the value is enqueued or the queue is
+    // closed
+    fn wake(&self, all: bool) {
+        // Pairs with the fence in recv, either we see the receiver sleeping or it sees our change
+        fence(SeqCst);
+        if self.sleepers.load(SeqCst) > 0 {
+            // Taking the lock makes sure the sleeper is either waiting or has not checked the
+            // queue yet, so the notification can not get lost
+            let _guard = self.lock.lock().unwrap();
+            if all {
+                self.condvar.notify_all();
+            } else {
+                self.condvar.notify_one();
+            }
+        }
+    }
+}
+
+pub struct Sender<T: 'static> {
+    chan: Arc<Chan<T>>,
+    handle: <HazardPointers as Reclaimer>::Handle,
+}
+
+pub struct Receiver<T: 'static> {
+    chan: Arc<Chan<T>>,
+    handle: <HazardPointers as Reclaimer>::Handle,
+}
+
+impl<T: 'static> Sender<T> {
+    /// Sends a value, fails and hands it back if all receivers have been dropped
+    pub fn send(&mut self, val: T) -> Result<(), SendError<T>> {
+        self.chan
+            .queue
+            .enqueue(val, &mut self.handle)
+            .map_err(|e| SendError(e.into_inner()))?;
+        self.chan.wake(false);
+        Ok(())
+    }
+}
+
+impl<T: 'static> Receiver<T> {
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        self.chan
+            .queue
+            .dequeue(&mut self.handle)
+            .map_err(|e| match e {
+                TryDequeueError::Empty => TryRecvError::Empty,
+                TryDequeueError::Closed => TryRecvError::Disconnected,
+            })
+    }
+
+    /// Waits for a value, fails once the channel is empty and all senders have been dropped.
+    ///
+    /// The receiver spins for a short while and then parks until a value is sent or the last
+    /// sender is dropped.
+    pub fn recv(&mut self) -> Result<T, RecvError> {
+        for _ in 0..SPIN_LIMIT {
+            match self.try_recv() {
+                Ok(val) => return Ok(val),
+                Err(TryRecvError::Disconnected) => return Err(RecvError),
+                Err(TryRecvError::Empty) => hint::spin_loop(),
+            }
+        }
+
+        let chan = self.chan.clone();
+        let mut guard = chan.lock.lock().unwrap();
+        chan.sleepers.fetch_add(1, SeqCst);
+        // Pairs with the fence in wake, either the sender sees us sleeping or we see its change
+        fence(SeqCst);
+        let res = loop {
+            match self.try_recv() {
+                Ok(val) => break Ok(val),
+                Err(TryRecvError::Disconnected) => break Err(RecvError),
+                Err(TryRecvError::Empty) => guard = chan.condvar.wait(guard).unwrap(),
+            }
+        };
+        chan.sleepers.fetch_sub(1, SeqCst);
+        res
+    }
+}
+
+impl<T: 'static> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.chan.senders.fetch_add(1, SeqCst);
+        Self {
+            chan: self.chan.clone(),
+            handle: HazardPointers::handle(),
+        }
+    }
+}
+
+impl<T: 'static> Clone for Receiver<T> {
+    fn clone(&self) -> Self {
+        self.chan.receivers.fetch_add(1, SeqCst);
+        Self {
+            chan: self.chan.clone(),
+            handle: HazardPointers::handle(),
+        }
+    }
+}
+
+impl<T: 'static> Drop for Sender<T> {
+    fn drop(&mut self) {
+        // The last sender closes the queue, receivers can still drain it
+        if self.chan.senders.fetch_sub(1, SeqCst) == 1 {
+            self.chan.queue.close(&mut self.handle);
+            self.chan.wake(true);
+        }
+    }
+}
+
+impl<T: 'static> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        // The last receiver closes the queue so further sends fail, anything left in it is
+        // dropped together with the channel
+        if self.chan.receivers.fetch_sub(1, SeqCst) == 1 {
+            self.chan.queue.close(&mut self.handle);
+        }
+    }
+}
+
+/// Returned by a failed send, hands the value back to the caller
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub struct SendError<T>(pub T);
+
+// Manual impl so values without Debug can still be unwrapped
+impl<T> fmt::Debug for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SendError(..)")
+    }
+}
+
+impl<T> fmt::Display for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("sending on a disconnected channel")
+    }
+}
+
+impl<T> Error for SendError<T> {}
+
+/// Returned by a failed try_recv
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TryRecvError {
+    /// The channel is currently empty, values may still be sent later
+    Empty,
+    /// The channel is empty and all senders have been dropped
+    Disconnected,
+}
+
+impl fmt::Display for TryRecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TryRecvError::Empty => f.write_str("receiving on an empty channel"),
+            TryRecvError::Disconnected => {
+                f.write_str("receiving on an empty and disconnected channel")
+            }
+        }
+    }
+}
+
+impl Error for TryRecvError {}
+
+/// Returned by recv once the channel is empty and all senders have been dropped
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct RecvError;
+
+impl fmt::Display for RecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("receiving on an empty and disconnected channel")
+    }
+}
+
+impl Error for RecvError {}
+
+#[cfg(test)]
+mod test {
+    use std::{sync::Arc, thread, time::Duration};
+
+    use super::{channel, channel_with_ring_size, RecvError, SendError, TryRecvError};
+
+    #[test]
+    fn basic() {
+        let (mut tx, mut rx) = channel_with_ring_size::<i32>(8);
+        for i in 0..100 {
+            tx.send(i).unwrap();
+        }
+        for i in 0..100 {
+            assert_eq!(rx.recv(), Ok(i));
+        }
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+    }
+
+    #[test]
+    fn disconnect_on_senders_dropped() {
+        let (mut tx, mut rx) = channel();
+        let mut tx2 = tx.clone();
+        tx.send(1).unwrap();
+        drop(tx);
+        tx2.send(2).unwrap();
+        drop(tx2);
+        assert_eq!(rx.recv(), Ok(1));
+        assert_eq!(rx.recv(), Ok(2));
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Disconnected));
+        assert_eq!(rx.recv(), Err(RecvError));
+    }
+
+    #[test]
+    fn wakes_parked_receiver() {
+        let (mut tx, mut rx) = channel::<i32>();
+        let handle = thread::spawn(move || rx.recv());
+        thread::sleep(Duration::from_millis(50));
+        tx.send(7).unwrap();
+        assert_eq!(handle.join().unwrap(), Ok(7));
+    }
+
+    #[test]
+    fn disconnect_wakes_parked_receivers() {
+        let (tx, rx) = channel::<i32>();
+        let handles: Vec<_> = (0..2)
+            .map(|_| {
+                let mut rx = rx.clone();
+                thread::spawn(move || rx.recv())
+            })
+            .collect();
+        thread::sleep(Duration::from_millis(50));
+        drop(tx);
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), Err(RecvError));
+        }
+    }
+
+    #[test]
+    fn disconnect_on_receivers_dropped() {
+        let (mut tx, rx) = channel();
+        let rx2 = rx.clone();
+        drop(rx);
+        tx.send(1).unwrap();
+        drop(rx2);
+        assert_eq!(tx.send(2), Err(SendError(2)));
+    }
+
+    #[test]
+    fn channel_concurrent() {
+        let (tx, rx) = channel_with_ring_size::<usize>(16);
+
+        let mut producers = vec![];
+        for i in 0..4 {
+            let mut tx = tx.clone();
+            let handle = thread::spawn(move || {
+                for j in 0..100 {
+                    tx.send(i * 100 + j).unwrap();
+                }
+            });
+            producers.push(handle);
+        }
+        drop(tx);
+
+        let mut consumers = vec![];
+        for _ in 0..4 {
+            let mut rx = rx.clone();
+            let handle = thread::spawn(move || {
+                let mut sum = 0;
+                // Runs until all producers are done and the channel is drained
+                while let Ok(v) = rx.recv() {
+                    sum += v;
+                }
+                sum
+            });
+            consumers.push(handle);
+        }
+        drop(rx);
+
+        for handle in producers {
+            handle.join().unwrap();
+        }
+        let sum: usize = consumers.into_iter().map(|h| h.join().unwrap()).sum();
+        assert_eq!(sum, 400 * 399 / 2, "Sums do not match!");
+    }
+
+    #[test]
+    fn dropping_drops_leftover_items() {
+        let item = Arc::new(());
+        let (mut tx, rx) = channel_with_ring_size::<Arc<()>>(8);
+        for _ in 0..25 {
+            tx.send(Arc::clone(&item)).unwrap();
+        }
+        assert_eq!(Arc::strong_count(&item), 26);
+        drop(tx);
+        drop(rx);
+        assert_eq!(Arc::strong_count(&item), 1);
+    }
+}
@@ -0,0 +1,4 @@
+use crate::reclaim::Epoch;
+
+// LPRQ with its segments reclaimed by crossbeam-epoch
+pub type SharedLPRQ<T> = super::lprq::SharedLPRQ<T, Epoch>;
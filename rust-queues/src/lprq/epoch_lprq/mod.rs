@@ -1,2 +0,0 @@
-pub mod lprq;
-mod prq;
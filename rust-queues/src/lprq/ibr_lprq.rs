@@ -0,0 +1,4 @@
+use crate::reclaim::Ibr;
+
+// LPRQ with its segments reclaimed by interval based reclamation
+pub type SharedLPRQ<T> = super::lprq::SharedLPRQ<T, Ibr>;
@@ -0,0 +1,185 @@
+use std::{
+    alloc,
+    ops::{Deref, DerefMut},
+    ptr::{self, NonNull},
+    slice,
+};
+
+// Both layouts assume 128 byte cache lines like the C++ reference, which also keeps the adjacent
+// line prefetcher on x86 from pulling in a neighbouring cell
+const CACHE_LINE: usize = 128;
+
+/// How the cells of a PRQ ring are laid out in memory, picked with the last type parameter of
+/// [`SharedLPRQ`](super::lprq::SharedLPRQ).
+pub trait Layout: 'static {
+    /// Storage of a single cell
+    type Slot<C: Default>: Default + Deref<Target = C>;
+
+    /// Position in the ring of the cell for `index`, in a ring of `1 << shift` cells that are
+    /// `cell_size` bytes each
+    fn remap(index: usize, shift: u32, cell_size: usize) -> usize;
+}
+
+/// Every cell is padded to a cache line of its own, so a ring of 1024 cells takes 128 KiB.
+pub struct Padded;
+
+/// Compact cells, with consecutive indices spread over different cache lines as in
+/// `CacheRemap.hpp` of the C++ reference. Neighbouring tickets still do not share a line as long
+/// as the ring spans at least as many lines as a line holds cells.
+pub struct Remapped;
+
+/// A cell on a cache line of its own.
+#[derive(Default)]
+#[repr(align(128))]
+pub struct PaddedSlot<C>(C);
+
+/// A cell without any padding.
+#[derive(Default)]
+pub struct CompactSlot<C>(C);
+
+impl<C> Deref for PaddedSlot<C> {
+    type Target = C;
+
+    fn deref(&self) -> &C {
+        &self.0
+    }
+}
+
+impl<C> Deref for CompactSlot<C> {
+    type Target = C;
+
+    fn deref(&self) -> &C {
+        &self.0
+    }
+}
+
+/// The slots of a ring. The allocation starts on a cache line boundary, which [`Remapped`] needs
+/// for its lines of cells to line up with the real ones.
+pub struct Ring<S> {
+    ptr: NonNull<S>,
+    len: usize,
+}
+
+// The ring owns its slots like a Box<[S]> would
+unsafe impl<S: Send> Send for Ring<S> {}
+unsafe impl<S: Sync> Sync for Ring<S> {}
+
+impl<S: Default> Ring<S> {
+    pub fn new(len: usize) -> Self {
+        let layout = Self::layout(len);
+        assert!(layout.size() > 0, "A ring needs at least one slot");
+        // Safety: The layout has a non-zero size
+        let ptr = unsafe { alloc::alloc(layout) }.cast::<S>();
+        let Some(ptr) = NonNull::new(ptr) else {
+            alloc::handle_alloc_error(layout);
+        };
+        for i in 0..len {
+            // Safety: In bounds of the allocation, which is not initialized yet
+            unsafe { ptr.as_ptr().add(i).write(S::default()) };
+        }
+        Ring { ptr, len }
+    }
+}
+
+impl<S> Ring<S> {
+    fn layout(len: usize) -> alloc::Layout {
+        alloc::Layout::array::<S>(len)
+            .and_then(|layout| layout.align_to(CACHE_LINE))
+            .expect("The ring is too large")
+    }
+}
+
+impl<S> Drop for Ring<S> {
+    fn drop(&mut self) {
+        // Safety: All slots were initialized in new, and the allocation was made with this layout
+        unsafe {
+            ptr::drop_in_place(ptr::slice_from_raw_parts_mut(self.ptr.as_ptr(), self.len));
+            alloc::dealloc(self.ptr.as_ptr().cast(), Self::layout(self.len));
+        }
+    }
+}
+
+impl<S> Deref for Ring<S> {
+    type Target = [S];
+
+    fn deref(&self) -> &[S] {
+        // Safety: All slots were initialized in new
+        unsafe { slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl<S> DerefMut for Ring<S> {
+    fn deref_mut(&mut self) -> &mut [S] {
+        // Safety: As above, and we have exclusive access
+        unsafe { slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl Layout for Padded {
+    type Slot<C: Default> = PaddedSlot<C>;
+
+    #[inline]
+    fn remap(index: usize, _shift: u32, _cell_size: usize) -> usize {
+        index
+    }
+}
+
+impl Layout for Remapped {
+    type Slot<C: Default> = CompactSlot<C>;
+
+    #[inline]
+    fn remap(index: usize, shift: u32, cell_size: usize) -> usize {
+        // Cell i goes to line i % lines at position i / lines, the sizes are all powers of two
+        let per_line_shift = (CACHE_LINE / cell_size).trailing_zeros();
+        if shift <= per_line_shift {
+            // The whole ring fits in a single line
+            return index;
+        }
+        let lines_shift = shift - per_line_shift;
+        ((index & ((1 << lines_shift) - 1)) << per_line_shift) | (index >> lines_shift)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::mem;
+
+    use super::{CompactSlot, Layout, PaddedSlot, Remapped, Ring, CACHE_LINE};
+
+    #[test]
+    fn padded_slots_fill_a_line() {
+        assert_eq!(mem::size_of::<PaddedSlot<[usize; 2]>>(), CACHE_LINE);
+    }
+
+    #[test]
+    fn ring_starts_on_a_line() {
+        for len in [1, 3, 64] {
+            let ring: Ring<CompactSlot<[usize; 2]>> = Ring::new(len);
+            assert_eq!(ring.as_ptr() as usize % CACHE_LINE, 0);
+            assert_eq!(ring.len(), len);
+        }
+    }
+
+    #[test]
+    fn remap_is_a_permutation() {
+        for shift in 0..12 {
+            let size = 1 << shift;
+            let mut seen = vec![false; size];
+            for index in 0..size {
+                let position = Remapped::remap(index, shift, 16);
+                assert!(!seen[position]);
+                seen[position] = true;
+            }
+        }
+    }
+
+    #[test]
+    fn remap_spreads_neighbours_over_lines() {
+        // 64 cells of 16 bytes are 8 lines of 8 cells
+        let lines: Vec<usize> = (0..8)
+            .map(|index| Remapped::remap(index, 6, 16) * 16 / CACHE_LINE)
+            .collect();
+        assert_eq!(lines, [0, 1, 2, 3, 4, 5, 6, 7]);
+        assert_eq!(Remapped::remap(8, 6, 16), 1);
+    }
+}
@@ -0,0 +1,4 @@
+use crate::reclaim::Leak;
+
+// LPRQ with its segments reclaimed by nothing, segments are leaked
+pub type SharedLPRQ<T> = super::lprq::SharedLPRQ<T, Leak>;
@@ -0,0 +1,272 @@
+// Model checks of the PRQ and LPRQ with loom. Besides the atomics from crate::sync, haphazard and
+// crossbeam-epoch switch to loom ones, the ibr reservations stay on std atomics and are not
+// checked. Run them with
+//
+//     RUSTFLAGS="--cfg loom --cfg crossbeam_loom" cargo test --release --lib lprq::loom_tests
+//
+// The rings are kept at one or two cells so segments fill up, close and get linked within a
+// handful of operations
+
+use std::sync::Arc as StdArc;
+
+use loom::{sync::Arc, thread};
+
+use super::{
+    lprq::{SharedLPRQ, LPRQ},
+    prq::PRQ,
+};
+use crate::{
+    reclaim::{Epoch, HazardPointers, Ibr, Leak, Reclaimer},
+    shared_queue::{TryDequeueError, TryEnqueueError},
+};
+
+// Every test runs once for each reclamation scheme except aarc. It has no loom support, so a segment
+// published through an AtomicArc is invisible to loom and every access to it looks like a race
+macro_rules! reclaimer_tests {
+    ($($name:ident),*) => {
+        $(
+            mod $name {
+                #[test]
+                fn hazard_pointers() {
+                    super::$name::<super::HazardPointers>();
+                }
+                #[test]
+                fn epoch() {
+                    super::$name::<super::Epoch>();
+                }
+                #[test]
+                fn leak() {
+                    super::$name::<super::Leak>();
+                }
+                #[test]
+                fn ibr() {
+                    super::$name::<super::Ibr>();
+                }
+            }
+        )*
+    };
+}
+
+reclaimer_tests!(
+    lprq_enqueue_dequeue,
+    lprq_concurrent_appends,
+    lprq_close_races_enqueue,
+    lprq_concurrent_drop
+);
+
+// Preemptions are bounded unless LOOM_MAX_PREEMPTIONS says otherwise, the hazard pointer domain
+// alone makes the exhaustive search run for hours
+fn model(f: impl Fn() + Sync + Send + 'static) {
+    let mut builder = loom::model::Builder::new();
+    if builder.preemption_bound.is_none() {
+        builder.preemption_bound = Some(2);
+    }
+    builder.check(f);
+}
+
+// Takes the value out of a pointer from PRQ::dequeue
+fn unbox(ptr: *mut usize) -> usize {
+    *unsafe { Box::from_raw(ptr) }
+}
+
+fn drain_prq(prq: &PRQ<usize>) -> Vec<usize> {
+    let mut values = vec![];
+    while let Ok(ptr) = prq.dequeue() {
+        values.push(unbox(ptr));
+    }
+    values
+}
+
+#[test]
+fn prq_enqueue_dequeue() {
+    model(|| {
+        let prq: Arc<PRQ<usize>> = Arc::new(PRQ::new(2));
+
+        let producer = {
+            let prq = Arc::clone(&prq);
+            thread::spawn(move || {
+                let mut enqueued = vec![];
+                for i in 1..=2 {
+                    let value = Box::into_raw(Box::new(i));
+                    match prq.enqueue(value) {
+                        Ok(()) => enqueued.push(i),
+                        // Burned cells can leave the ring looking full
+                        Err(_) => drop(unsafe { Box::from_raw(value) }),
+                    }
+                }
+                enqueued
+            })
+        };
+        // A dequeue that overtakes the enqueue burns the cell, and the dequeue that finds the
+        // ring empty has to fix the tail up behind it
+        let first = prq.dequeue().ok().map(unbox);
+        let enqueued = producer.join().unwrap();
+
+        let mut values: Vec<usize> = first.into_iter().collect();
+        values.extend(drain_prq(&prq));
+        assert_eq!(values, enqueued);
+    });
+}
+
+#[test]
+fn prq_concurrent_enqueues() {
+    model(|| {
+        let prq: Arc<PRQ<usize>> = Arc::new(PRQ::new(2));
+
+        let producers: Vec<_> = (1..=2)
+            .map(|i| {
+                let prq = Arc::clone(&prq);
+                thread::spawn(move || {
+                    // Each thread writes its own token into the cell it claims
+                    assert_eq!(prq.enqueue(Box::into_raw(Box::new(i))), Ok(()));
+                })
+            })
+            .collect();
+        for producer in producers {
+            producer.join().unwrap();
+        }
+
+        let mut values = drain_prq(&prq);
+        values.sort();
+        assert_eq!(values, [1, 2]);
+    });
+}
+
+#[test]
+fn prq_close_races_enqueue() {
+    model(|| {
+        let prq: Arc<PRQ<usize>> = Arc::new(PRQ::new(2));
+
+        let producer = {
+            let prq = Arc::clone(&prq);
+            thread::spawn(move || {
+                let value = Box::into_raw(Box::new(1));
+                match prq.enqueue(value) {
+                    Ok(()) => true,
+                    Err(_) => {
+                        drop(unsafe { Box::from_raw(value) });
+                        false
+                    }
+                }
+            })
+        };
+        prq.close();
+        let enqueued = producer.join().unwrap();
+
+        let values = drain_prq(&prq);
+        assert_eq!(values, if enqueued { vec![1] } else { vec![] });
+        // Closed rings stay closed once drained
+        assert_eq!(prq.dequeue(), Err(TryDequeueError::Closed));
+    });
+}
+
+fn lprq_enqueue_dequeue<R: Reclaimer>() {
+    model(|| {
+        // Every enqueue after the first links in a new segment
+        let queue: Arc<LPRQ<usize, R>> = Arc::new(LPRQ::new(1));
+
+        let producer = {
+            let queue = Arc::clone(&queue);
+            thread::spawn(move || {
+                let mut handle = R::handle();
+                for i in 1..=2 {
+                    queue.enqueue(i, &mut handle).unwrap();
+                }
+            })
+        };
+        let mut handle = R::handle();
+        let mut values: Vec<usize> = queue.dequeue(&mut handle).ok().into_iter().collect();
+        producer.join().unwrap();
+
+        while let Ok(value) = queue.dequeue(&mut handle) {
+            values.push(value);
+        }
+        assert_eq!(values, [1, 2]);
+    });
+}
+
+fn lprq_concurrent_appends<R: Reclaimer>() {
+    model(|| {
+        let queue: Arc<LPRQ<usize, R>> = Arc::new(LPRQ::new(1));
+        let mut handle = R::handle();
+        // Fill the first segment so both producers race to append the next one
+        queue.enqueue(0, &mut handle).unwrap();
+
+        let producers: Vec<_> = (1..=2)
+            .map(|i| {
+                let queue = Arc::clone(&queue);
+                thread::spawn(move || {
+                    let mut handle = R::handle();
+                    queue.enqueue(i, &mut handle).unwrap();
+                })
+            })
+            .collect();
+        for producer in producers {
+            producer.join().unwrap();
+        }
+
+        let mut values = vec![];
+        while let Ok(value) = queue.dequeue(&mut handle) {
+            values.push(value);
+        }
+        values.sort();
+        assert_eq!(values, [0, 1, 2]);
+    });
+}
+
+fn lprq_close_races_enqueue<R: Reclaimer>() {
+    model(|| {
+        let queue: Arc<LPRQ<usize, R>> = Arc::new(LPRQ::new(1));
+        let mut handle = R::handle();
+        queue.enqueue(0, &mut handle).unwrap();
+
+        let producer = {
+            let queue = Arc::clone(&queue);
+            thread::spawn(move || {
+                let mut handle = R::handle();
+                match queue.enqueue(1, &mut handle) {
+                    Ok(()) => true,
+                    Err(TryEnqueueError::Closed(1)) => false,
+                    Err(e) => panic!("Unexpected enqueue error {e:?}"),
+                }
+            })
+        };
+        // The tail is full, so closing links in the finalized segment while the producer tries
+        // to link in a new one
+        queue.close(&mut handle);
+        let enqueued = producer.join().unwrap();
+
+        let mut values = vec![];
+        let error = loop {
+            match queue.dequeue(&mut handle) {
+                Ok(value) => values.push(value),
+                Err(e) => break e,
+            }
+        };
+        assert_eq!(error, TryDequeueError::Closed);
+        assert_eq!(values, if enqueued { vec![0, 1] } else { vec![0] });
+    });
+}
+
+fn lprq_concurrent_drop<R: Reclaimer>() {
+    model(|| {
+        let item = StdArc::new(());
+        let queue: SharedLPRQ<StdArc<()>, R> = SharedLPRQ::with_ring_size(1);
+
+        let other = {
+            let mut queue = queue.clone();
+            let item = StdArc::clone(&item);
+            thread::spawn(move || {
+                queue.try_enqueue(StdArc::clone(&item)).unwrap();
+                let _ = queue.try_dequeue();
+                // The last handle dropped frees the queue and whatever is left in it
+            })
+        };
+        let mut queue = queue;
+        queue.try_enqueue(StdArc::clone(&item)).unwrap();
+        drop(queue);
+        other.join().unwrap();
+
+        assert_eq!(StdArc::strong_count(&item), 1);
+    });
+}
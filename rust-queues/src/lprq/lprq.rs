@@ -1,172 +1,405 @@
-use std::{ptr, sync::Arc};
-
-use haphazard::{AtomicPtr, HazardPointer};
+use std::ptr;
 
 use crossbeam_utils::CachePadded;
 
-use crate::shared_queue::SharedQueue;
+use crate::{
+    reclaim::{HazardPointers, Reclaimer, Recycle},
+    shared_queue::{BatchQueue, SharedQueue, TryDequeueError, TryEnqueueError},
+    sync::Arc,
+};
+
+use super::{
+    layout::{Layout, Padded},
+    pool::{PoolStats, SegmentPool},
+    prq::PRQ,
+};
 
-use super::prq::PRQ;
+/// Number of cells in the ring segments of an LPRQ created with `new`
+pub const DEFAULT_RING_SIZE: usize = 1024;
 
-pub struct SharedLPRQ<'a, T, const N: usize> {
-    queue: Arc<LPRQ<T, N>>,
-    hazard1: HazardPointer<'a>,
-    hazard2: HazardPointer<'a>,
+pub struct SharedLPRQ<T: 'static, R: Reclaimer = HazardPointers, L: Layout = Padded> {
+    queue: Arc<LPRQ<T, R, L>>,
+    handle: R::Handle,
 }
 
-impl<'a, T, const N: usize> SharedQueue<T> for SharedLPRQ<'a, T, N> {
-    fn new() -> Self {
+impl<T, R: Reclaimer, L: Layout> SharedLPRQ<T, R, L> {
+    /// Creates a queue whose ring segments have `ring_size` cells, which must be a power of two.
+    pub fn with_ring_size(ring_size: usize) -> Self {
+        Self {
+            queue: Arc::new(LPRQ::new(ring_size)),
+            handle: R::handle(),
+        }
+    }
+
+    /// Creates a queue that keeps up to `capacity` reclaimed segments around for reuse, instead
+    /// of allocating a new one every time the tail fills up. Reclamation schemes that never
+    /// reclaim on behalf of the queue, like [`Aarc`](crate::reclaim::Aarc), never fill the pool.
+    pub fn with_segment_pool(ring_size: usize, capacity: usize) -> Self {
         Self {
-            queue: Arc::new(LPRQ::new()),
-            hazard1: HazardPointer::new(),
-            hazard2: HazardPointer::new(),
+            queue: Arc::new(LPRQ::with_segment_pool(ring_size, capacity)),
+            handle: R::handle(),
         }
     }
 
-    fn enqueue(&mut self, val: *const T) {
-        self.queue.enqueue(val, &mut self.hazard1)
+    // Counters of the segment pool, None if the queue has none
+    pub fn pool_stats(&self) -> Option<PoolStats> {
+        self.queue.pool_stats()
+    }
+
+    pub fn try_enqueue(&mut self, val: T) -> Result<(), TryEnqueueError<T>> {
+        self.queue.enqueue(val, &mut self.handle)
+    }
+
+    pub fn try_dequeue(&mut self) -> Result<T, TryDequeueError> {
+        self.queue.dequeue(&mut self.handle)
+    }
+
+    // Enqueues the values in order, Err() holds the values that were not enqueued
+    pub fn enqueue_batch(
+        &mut self,
+        vals: impl IntoIterator<Item = T>,
+    ) -> Result<(), TryEnqueueError<Vec<T>>> {
+        self.queue.enqueue_batch(vals, &mut self.handle)
+    }
+
+    // Dequeues up to max values into out, returns how many were dequeued
+    pub fn dequeue_batch(
+        &mut self,
+        out: &mut Vec<T>,
+        max: usize,
+    ) -> Result<usize, TryDequeueError> {
+        self.queue.dequeue_batch(out, max, &mut self.handle)
+    }
+
+    // Approximate number of items in the queue
+    pub fn len(&mut self) -> usize {
+        self.queue.len(&mut self.handle)
+    }
+
+    pub fn is_empty(&mut self) -> bool {
+        self.queue.is_empty(&mut self.handle)
+    }
+
+    // Rejects all further enqueues, consumers can still drain what is left and get
+    // TryDequeueError::Closed once the queue is empty
+    pub fn close(&mut self) {
+        self.queue.close(&mut self.handle)
+    }
+}
+
+impl<T, R: Reclaimer, L: Layout> SharedQueue<T> for SharedLPRQ<T, R, L> {
+    fn new() -> Self {
+        Self::with_ring_size(DEFAULT_RING_SIZE)
+    }
+
+    fn enqueue(&mut self, val: T) {
+        let _ = self.try_enqueue(val);
+    }
+
+    fn dequeue(&mut self) -> Option<T> {
+        self.try_dequeue().ok()
+    }
+}
+
+impl<T, R: Reclaimer, L: Layout> BatchQueue<T> for SharedLPRQ<T, R, L> {
+    fn enqueue_batch(&mut self, vals: Vec<T>) {
+        let _ = SharedLPRQ::enqueue_batch(self, vals);
     }
 
-    fn dequeue(&mut self) -> Option<*const T> {
-        self.queue.dequeue(&mut self.hazard1, &mut self.hazard2)
+    fn dequeue_batch(&mut self, out: &mut Vec<T>, max: usize) -> usize {
+        SharedLPRQ::dequeue_batch(self, out, max).unwrap_or(0)
     }
 }
 
-impl<'a, T, const N: usize> Clone for SharedLPRQ<'a, T, N> {
+impl<T, R: Reclaimer, L: Layout> Clone for SharedLPRQ<T, R, L> {
     fn clone(&self) -> Self {
         Self {
             queue: self.queue.clone(),
-            hazard1: HazardPointer::new(),
-            hazard2: HazardPointer::new(),
+            handle: R::handle(),
         }
     }
 }
 
-struct LPRQ<T, const N: usize> {
-    head: CachePadded<AtomicPtr<PRQ<T, N>>>,
-    tail: CachePadded<AtomicPtr<PRQ<T, N>>>,
+#[allow(clippy::upper_case_acronyms)] // Named as in the paper
+pub(crate) struct LPRQ<T: 'static, R: Reclaimer = HazardPointers, L: Layout = Padded> {
+    head: CachePadded<R::Atomic<PRQ<T, R, L>>>,
+    tail: CachePadded<R::Atomic<PRQ<T, R, L>>>,
+    pool: Option<Arc<SegmentPool<T, R, L>>>,
+    ring_size: usize,
 }
 
-impl<T, const N: usize> Drop for LPRQ<T, N> {
+// The queue owns the boxed values stored in its segments, so it can only be shared between threads
+// if the values themselves can be sent between them
+unsafe impl<T: Send, R: Reclaimer, L: Layout> Send for LPRQ<T, R, L> {}
+unsafe impl<T: Send, R: Reclaimer, L: Layout> Sync for LPRQ<T, R, L> {}
+
+impl<T, R: Reclaimer, L: Layout> Drop for LPRQ<T, R, L> {
     fn drop(&mut self) {
         // Empty the queue to drop any leftover items
-        let mut hazard1 = HazardPointer::new();
-        let mut hazard2 = HazardPointer::new();
-        while let Some(_) = self.dequeue(&mut hazard1, &mut hazard2) {}
+        let mut handle = R::handle();
+        while self.dequeue(&mut handle).is_ok() {}
 
-        let head = self.head.load_ptr();
-        let tail = self.tail.load_ptr();
+        let head = R::load_ptr(&self.head);
+        let tail = R::load_ptr(&self.tail);
         // The queue should be empty now, but dubblecheck for safety
         if head == tail {
-            let old =  unsafe { self.head.swap_ptr(ptr::null_mut())}.expect("A LPRQ with both head and tail as null was dropped. This should never happen and indicates a bug or memory corruption");
-            unsafe { old.retire() };
+            unsafe { R::free(&mut self.head) };
         } else {
             panic!("Drop for LPRQ somehow failed to dequeue all its items")
         }
     }
 }
 
-impl<T, const N: usize> LPRQ<T, N> {
-    fn new() -> Self {
-        let initial: *mut PRQ<T, N> = Box::into_raw(Box::new(PRQ::new()));
-        Self {
-            head: unsafe { AtomicPtr::new(initial) }.into(),
-            tail: unsafe { AtomicPtr::new(initial) }.into(),
+impl<T, R: Reclaimer, L: Layout> LPRQ<T, R, L> {
+    pub(crate) fn new(ring_size: usize) -> Self {
+        Self::with_pool(ring_size, None)
+    }
+    pub(crate) fn with_segment_pool(ring_size: usize, capacity: usize) -> Self {
+        Self::with_pool(ring_size, Some(Arc::new(SegmentPool::new(capacity))))
+    }
+    fn with_pool(ring_size: usize, pool: Option<Arc<SegmentPool<T, R, L>>>) -> Self {
+        assert!(
+            ring_size.is_power_of_two(),
+            "The ring size must be a power of two"
+        );
+        let mut lprq = Self {
+            head: R::null().into(),
+            tail: R::null().into(),
+            pool,
+            ring_size,
+        };
+        let initial = lprq.new_segment(0);
+        *lprq.head = unsafe { R::atomic(initial) };
+        *lprq.tail = unsafe { R::atomic(initial) };
+        unsafe { R::release(initial) };
+        lprq
+    }
+    pub(crate) fn pool_stats(&self) -> Option<PoolStats> {
+        self.pool.as_ref().map(|pool| pool.stats())
+    }
+    // A new and empty segment owned by the caller, taken from the pool if possible
+    fn new_segment(&self, index: usize) -> *mut PRQ<T, R, L> {
+        let Some(pool) = &self.pool else {
+            let mut prq = PRQ::new(self.ring_size);
+            prq.index = index;
+            return R::alloc(prq);
+        };
+        let segment = match pool.take() {
+            Some(segment) => {
+                // Safety: Segments in the pool are not reachable by anyone else
+                unsafe { (*segment).reset() };
+                segment
+            }
+            None => R::alloc(PRQ::new(self.ring_size)),
+        };
+        unsafe {
+            (*segment).index = index;
+            (*segment).pool = Some(pool.clone());
+        }
+        segment
+    }
+    // Links in the new segment after queue, or moves the tail on if another one got there first.
+    // Returns false if the new segment was not linked in, it is then given back to the pool or
+    // freed again
+    fn append(
+        &self,
+        guard: &mut R::Guard<'_, PRQ<T, R, L>>,
+        queue_ptr: *mut PRQ<T, R, L>,
+        new_tail_ptr: *mut PRQ<T, R, L>,
+    ) -> bool {
+        // Safety: The caller has protected the queue in the first slot
+        let queue = unsafe { &*queue_ptr };
+        if unsafe { R::compare_exchange(guard, &queue.next, ptr::null_mut(), new_tail_ptr) } {
+            // Next successfully inserted, update tail to point to that
+            unsafe {
+                R::compare_exchange(guard, &self.tail, queue_ptr, new_tail_ptr);
+                R::release(new_tail_ptr);
+            }
+            return true;
         }
+        let next = R::protect(guard, 1, &queue.next);
+        unsafe { R::compare_exchange(guard, &self.tail, queue_ptr, next) };
+        // Recycle the failed new tail so it does not leak, it was never shared
+        unsafe { PRQ::recycle(new_tail_ptr) };
+        false
     }
-    fn enqueue(&self, val: *const T, hazard: &mut HazardPointer) {
+    pub(crate) fn enqueue(&self, val: T, handle: &mut R::Handle) -> Result<(), TryEnqueueError<T>> {
+        let val: *const T = Box::into_raw(Box::new(val));
+        let mut guard = R::pin(handle);
         loop {
             // fast path: Add item to current PRQ
-            let queue = self.tail.safe_load(hazard).unwrap();
-            let queue_ptr: *const PRQ<T, N> = queue;
+            let queue_ptr = R::protect(&mut guard, 0, &self.tail);
+            // Safety: The tail is never null and is protected by the guard
+            let queue = unsafe { &*queue_ptr };
             match queue.enqueue(val) {
-                Ok(_) => {
-                    return
-                },
+                Ok(_) => return Ok(()),
+                Err(_) if queue.is_finalized() => {
+                    // The LPRQ has been closed, hand the value back
+                    let val = unsafe { Box::from_raw(val.cast_mut()) };
+                    return Err(TryEnqueueError::Closed(*val));
+                }
                 Err(_) => {
                     // Slow path: Tail is full, allocate and add a new crq
-                    let new_tail: AtomicPtr<PRQ<T, N>> =
-                        AtomicPtr::from(Box::new(PRQ::new_with_item(val)));
-                    let new_tail_ptr = new_tail.load_ptr();
-                    match unsafe {
-                        queue
-                            .next
-                            .compare_exchange_ptr(ptr::null_mut(), new_tail_ptr)
-                    } {
-                        Ok(_) => {
-                            // Next successfully inserted, update tail to point to that
-                            let _ = unsafe {
-                                self.tail
-                                    .compare_exchange_ptr(queue_ptr.cast_mut(), new_tail_ptr)
-                            };
-                            return;
-                        }
-                        Err(next) => {
-                            let _ = unsafe {
-                                self.tail.compare_exchange_ptr(queue_ptr.cast_mut(), next)
-                            };
-                            // Drop the failed new tail so it does not leak
-                            let _ = unsafe { new_tail.retire() };
-                            continue;
-                        }
+                    let new_prq = self.new_segment(queue.index + 1);
+                    // Safety: The new segment is not shared yet
+                    unsafe { &*new_prq }.enqueue(val).expect(
+                        "Failed to enqueue an item in a new and empty PRQ, Should not happen ever",
+                    );
+                    if self.append(&mut guard, queue_ptr, new_prq) {
+                        return Ok(());
+                    }
+                }
+            }
+        }
+    }
+    pub(crate) fn enqueue_batch(
+        &self,
+        vals: impl IntoIterator<Item = T>,
+        handle: &mut R::Handle,
+    ) -> Result<(), TryEnqueueError<Vec<T>>> {
+        let vals: Vec<*const T> = vals
+            .into_iter()
+            .map(|val| Box::into_raw(Box::new(val)).cast_const())
+            .collect();
+        let mut rest: &[*const T] = &vals;
+        let mut guard = R::pin(handle);
+        while !rest.is_empty() {
+            // fast path: Add items to current PRQ
+            let queue_ptr = R::protect(&mut guard, 0, &self.tail);
+            // Safety: The tail is never null and is protected by the guard
+            let queue = unsafe { &*queue_ptr };
+            match queue.enqueue_batch(rest) {
+                Ok(_) => return Ok(()),
+                Err(e) if queue.is_finalized() => {
+                    // The LPRQ has been closed, hand the values back
+                    let vals = e
+                        .into_inner()
+                        .iter()
+                        .map(|val| *unsafe { Box::from_raw(val.cast_mut()) })
+                        .collect();
+                    return Err(TryEnqueueError::Closed(vals));
+                }
+                Err(e) => {
+                    rest = e.into_inner();
+                    // Slow path: Tail is full, allocate and add a new crq holding as many of the
+                    // remaining items as fit
+                    let taken = rest.len().min(self.ring_size);
+                    let new_prq = self.new_segment(queue.index + 1);
+                    // Safety: The new segment is not shared yet
+                    unsafe { &*new_prq }.enqueue_batch(&rest[..taken]).expect(
+                        "Failed to enqueue items in a new and empty PRQ, Should not happen ever",
+                    );
+                    if self.append(&mut guard, queue_ptr, new_prq) {
+                        rest = &rest[taken..];
                     }
                 }
             }
         }
+        Ok(())
+    }
+    pub(crate) fn close(&self, handle: &mut R::Handle) {
+        // Link in an empty and already closed PRQ as the last segment, it is never enqueued into
+        // and nothing can be linked after it
+        let mut guard = R::pin(handle);
+        loop {
+            let queue_ptr = R::protect(&mut guard, 0, &self.tail);
+            // Safety: The tail is never null and is protected by the guard
+            let queue = unsafe { &*queue_ptr };
+            if queue.is_finalized() {
+                // Already closed
+                return;
+            }
+            // Close the current tail first so no enqueue can land in it after we return
+            queue.close();
+            let mut last = PRQ::new_finalized();
+            last.index = queue.index + 1;
+            if self.append(&mut guard, queue_ptr, R::alloc(last)) {
+                return;
+            }
+        }
+    }
+    pub(crate) fn dequeue(&self, handle: &mut R::Handle) -> Result<T, TryDequeueError> {
+        let mut guard = R::pin(handle);
+        loop {
+            let queue_ptr = R::protect(&mut guard, 0, &self.head);
+            // Safety: The head is never null and is protected by the guard
+            let queue = unsafe { &*queue_ptr };
+            if let Ok(v) = queue.dequeue() {
+                return Ok(*unsafe { Box::from_raw(v) });
+            }
+            // Failed, is this queue empty?
+            let next_ptr = R::protect(&mut guard, 1, &queue.next);
+            if next_ptr.is_null() {
+                if queue.is_finalized() {
+                    // Queue is empty and closed
+                    return Err(TryDequeueError::Closed);
+                }
+                // Queue is empty
+                return Err(TryDequeueError::Empty);
+            }
+            // LPRQ is not empty, try to dequeue again
+            if let Ok(v) = queue.dequeue() {
+                return Ok(*unsafe { Box::from_raw(v) });
+            }
+            // PRQ is empty, update head and restart
+            if unsafe { R::compare_exchange(&guard, &self.head, queue_ptr, next_ptr) } {
+                // The old PRQ is now empty, so we retire it
+                unsafe { R::recycle(&guard, queue_ptr) };
+            }
+        }
+    }
+    // Segments between the head and the tail are not dequeued from, so they are counted as full.
+    // Tickets burned by failed operations are counted as items, so the result can be a little high
+    pub(crate) fn len(&self, handle: &mut R::Handle) -> usize {
+        let mut guard = R::pin(handle);
+        // Safety: The head and tail are never null and are protected by the guard
+        let head = unsafe { &*R::protect(&mut guard, 0, &self.head) };
+        let tail = unsafe { &*R::protect(&mut guard, 1, &self.tail) };
+        if tail.index <= head.index {
+            // The tail can lag behind the head
+            return head.len();
+        }
+        head.len() + (tail.index - head.index - 1) * self.ring_size + tail.len()
     }
-    fn dequeue(
+    pub(crate) fn is_empty(&self, handle: &mut R::Handle) -> bool {
+        self.len(handle) == 0
+    }
+    pub(crate) fn dequeue_batch(
         &self,
-        hazard1: &mut HazardPointer,
-        hazard2: &mut HazardPointer,
-    ) -> Option<*const T> {
+        out: &mut Vec<T>,
+        max: usize,
+        handle: &mut R::Handle,
+    ) -> Result<usize, TryDequeueError> {
+        let mut values: Vec<*mut T> = Vec::new();
+        let mut guard = R::pin(handle);
         loop {
-            let queue = self.head.safe_load(hazard1).unwrap();
-            match queue.dequeue() {
-                Some(v) => {
-                    return Some(v);
+            let queue_ptr = R::protect(&mut guard, 0, &self.head);
+            // Safety: The head is never null and is protected by the guard
+            let queue = unsafe { &*queue_ptr };
+            if queue.dequeue_batch(&mut values, max).is_err() {
+                // Failed, is this queue empty?
+                let next_ptr = R::protect(&mut guard, 1, &queue.next);
+                if next_ptr.is_null() {
+                    if queue.is_finalized() {
+                        // Queue is empty and closed
+                        return Err(TryDequeueError::Closed);
+                    }
+                    // Queue is empty
+                    return Err(TryDequeueError::Empty);
                 }
-                None => {
-                    // Failed, is this queue empty?
-                    match hazard2.protect_ptr(unsafe { queue.next.as_std() }) {
-                        Some(next_ptr) => {
-                            // LPRQ is not empty, try to dequeue again
-                            match queue.dequeue() {
-                                Some(value) => {
-                                    return Some(value);
-                                }
-                                None => {
-                                    // PRQ is empty, update head and restart
-                                    let queue_ptr: *const PRQ<T, N> = queue;
-                                    match unsafe {
-                                        self.head.compare_exchange_ptr(
-                                            queue_ptr.cast_mut(),
-                                            next_ptr.0.as_ptr(),
-                                        )
-                                    } {
-                                        Ok(Some(old)) => {
-                                            // The old PRQ is now empty, so we retire it
-                                            unsafe { old.retire() };
-                                            continue;
-                                        }
-                                        Ok(None) => {
-                                            // Null ptr somehow made it here, should be impossible
-                                            panic!("Queue somehow turned into a null pointer despite it being used before, should be impossible")
-                                        }
-                                        Err(_) => {
-                                            // Update failed, we are entierly out of sync so just restart
-                                            continue;
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                        None => {
-                            // Queue is empty
-                            return None;
-                        }
+                // LPRQ is not empty, try to dequeue again
+                if queue.dequeue_batch(&mut values, max).is_err() {
+                    // PRQ is empty, update head and restart
+                    if unsafe { R::compare_exchange(&guard, &self.head, queue_ptr, next_ptr) } {
+                        // The old PRQ is now empty, so we retire it
+                        unsafe { R::recycle(&guard, queue_ptr) };
                     }
+                    continue;
                 }
             }
+            let count = values.len();
+            out.extend(values.into_iter().map(|v| *unsafe { Box::from_raw(v) }));
+            return Ok(count);
         }
     }
 }
@@ -175,45 +408,84 @@ impl<T, const N: usize> LPRQ<T, N> {
 mod test {
     use std::{sync::Arc, thread};
 
-    use haphazard::{Domain, HazardPointer};
+    use haphazard::Domain;
 
     use super::LPRQ;
-
-    const NUMBERS: [i32;100] = {
-        let mut output = [0;100];
-        let mut i = 0;
-        while i < 100 {
-            output[i as usize] = i;
-            i += 1;
-        }
-        output
+    use crate::{
+        lprq::layout::Remapped,
+        reclaim::{Aarc, Epoch, HazardPointers, Ibr, Leak, Reclaimer},
+        shared_queue::{TryDequeueError, TryEnqueueError},
     };
-    #[test]
-    fn basic() {
-        let queue: LPRQ<i32, 10> = LPRQ::new();
-        let mut hazard = HazardPointer::new();
-        for i in NUMBERS {
-            queue.enqueue((&NUMBERS[i as usize]) as *const _, &mut hazard);
+
+    // Every test runs once for each reclamation scheme
+    macro_rules! reclaimer_tests {
+        ($($name:ident),*) => {
+            $(
+                mod $name {
+                    #[test]
+                    fn hazard_pointers() {
+                        super::$name::<super::HazardPointers>();
+                    }
+                    #[test]
+                    fn epoch() {
+                        super::$name::<super::Epoch>();
+                    }
+                    #[test]
+                    fn aarc() {
+                        super::$name::<super::Aarc>();
+                    }
+                    #[test]
+                    fn leak() {
+                        super::$name::<super::Leak>();
+                    }
+                    #[test]
+                    fn ibr() {
+                        super::$name::<super::Ibr>();
+                    }
+                }
+            )*
+        };
+    }
+
+    reclaimer_tests!(
+        basic,
+        basic_concurrent,
+        dropping_with_non_empty,
+        dropping_drops_leftover_items,
+        close_drains_then_reports_closed,
+        close_concurrent,
+        batch,
+        batch_concurrent,
+        len,
+        segment_pool,
+        segment_pool_concurrent,
+        ring_sizes,
+        remapped_concurrent
+    );
+
+    fn basic<R: Reclaimer>() {
+        let queue: LPRQ<i32, R> = LPRQ::new(8);
+        let mut handle = R::handle();
+        for i in 0..100 {
+            queue.enqueue(i, &mut handle).unwrap();
         }
-        let mut hazard2 = HazardPointer::new();
-        for i in NUMBERS {
-            let v = queue.dequeue(&mut hazard, &mut hazard2).unwrap();
-            assert_eq!(unsafe {*v}, NUMBERS[i as usize]);
+        for i in 0..100 {
+            let v = queue.dequeue(&mut handle).unwrap();
+            assert_eq!(v, i);
         }
     }
 
-    #[test]
-    fn basic_concurrent() {
-        let queue: Arc<LPRQ<i32, 10>> = Arc::new(LPRQ::new());
+    fn basic_concurrent<R: Reclaimer>() {
+        let queue: Arc<LPRQ<i32, R>> = Arc::new(LPRQ::new(8));
 
         let mut handles = vec![];
 
         for i in 0..10 {
             let queue = Arc::clone(&queue);
             let handle = thread::spawn(move || {
-                let mut hazard = HazardPointer::new();
+                let mut handle = R::handle();
                 for j in 0..10 {
-                    queue.enqueue(&NUMBERS[j + i], &mut hazard)
+                    queue.enqueue(j + i, &mut handle).unwrap();
                 }
             });
             handles.push(handle);
@@ -228,10 +500,9 @@ mod test {
         for _i in 0..10 {
             let queue = Arc::clone(&queue);
             let handle = thread::spawn(move || {
-                let mut hazard1 = HazardPointer::new();
-                let mut hazard2 = HazardPointer::new();
+                let mut handle = R::handle();
                 for _j in 0..10 {
-                    queue.dequeue(&mut hazard1, &mut hazard2).unwrap();
+                    queue.dequeue(&mut handle).unwrap();
                 }
             });
             handles.push(handle);
@@ -242,18 +513,18 @@ mod test {
         drop(queue);
         Domain::global().eager_reclaim();
     }
-    #[test]
-    fn dropping_with_non_empty() {
-        let queue: Arc<LPRQ<i32, 10>> = Arc::new(LPRQ::new());
+
+    fn dropping_with_non_empty<R: Reclaimer>() {
+        let queue: Arc<LPRQ<i32, R>> = Arc::new(LPRQ::new(8));
 
         let mut handles = vec![];
 
         for i in 0..10 {
             let queue = Arc::clone(&queue);
             let handle = thread::spawn(move || {
-                let mut hazard = HazardPointer::new();
+                let mut handle = R::handle();
                 for j in 0..10 {
-                    queue.enqueue(&NUMBERS[j + i], &mut hazard)
+                    queue.enqueue(j + i, &mut handle).unwrap();
                 }
             });
             handles.push(handle);
@@ -268,10 +539,9 @@ mod test {
         for _i in 0..10 {
             let queue = Arc::clone(&queue);
             let handle = thread::spawn(move || {
-                let mut hazard1 = HazardPointer::new();
-                let mut hazard2 = HazardPointer::new();
+                let mut handle = R::handle();
                 for _j in 0..5 {
-                    queue.dequeue(&mut hazard1, &mut hazard2).unwrap();
+                    queue.dequeue(&mut handle).unwrap();
                 }
             });
             handles.push(handle);
@@ -282,4 +552,297 @@ mod test {
         drop(queue);
         Domain::global().eager_reclaim();
     }
+
+    fn dropping_drops_leftover_items<R: Reclaimer>() {
+        let item = Arc::new(());
+        let queue: LPRQ<Arc<()>, R> = LPRQ::new(8);
+        let mut handle = R::handle();
+        for _ in 0..25 {
+            queue.enqueue(Arc::clone(&item), &mut handle).unwrap();
+        }
+        drop(handle);
+        assert_eq!(Arc::strong_count(&item), 26);
+        drop(queue);
+        Domain::global().eager_reclaim();
+        assert_eq!(Arc::strong_count(&item), 1);
+    }
+
+    fn close_drains_then_reports_closed<R: Reclaimer>() {
+        let queue: LPRQ<i32, R> = LPRQ::new(8);
+        let mut handle = R::handle();
+        for i in 0..25 {
+            queue.enqueue(i, &mut handle).unwrap();
+        }
+        queue.close(&mut handle);
+        assert_eq!(
+            queue.enqueue(25, &mut handle),
+            Err(TryEnqueueError::Closed(25))
+        );
+        // Closing twice is fine
+        queue.close(&mut handle);
+        for i in 0..25 {
+            assert_eq!(queue.dequeue(&mut handle), Ok(i));
+        }
+        assert_eq!(queue.dequeue(&mut handle), Err(TryDequeueError::Closed));
+        assert_eq!(
+            queue.enqueue(26, &mut handle),
+            Err(TryEnqueueError::Closed(26))
+        );
+    }
+
+    fn close_concurrent<R: Reclaimer>() {
+        let queue: Arc<LPRQ<usize, R>> = Arc::new(LPRQ::new(8));
+
+        let mut handles = vec![];
+
+        for i in 0..4 {
+            let queue = Arc::clone(&queue);
+            let handle = thread::spawn(move || {
+                let mut handle = R::handle();
+                let mut enqueued = 0;
+                for j in 0..100 {
+                    if queue.enqueue(i * 100 + j, &mut handle).is_ok() {
+                        enqueued += 1;
+                    }
+                }
+                enqueued
+            });
+            handles.push(handle);
+        }
+
+        let mut handle = R::handle();
+        let mut dequeued = 0;
+        while dequeued < 50 {
+            if queue.dequeue(&mut handle).is_ok() {
+                dequeued += 1;
+            }
+        }
+        queue.close(&mut handle);
+
+        let enqueued: usize = handles.into_iter().map(|h| h.join().unwrap()).sum();
+        loop {
+            match queue.dequeue(&mut handle) {
+                Ok(_) => dequeued += 1,
+                Err(e) => {
+                    assert_eq!(e, TryDequeueError::Closed);
+                    break;
+                }
+            }
+        }
+        assert_eq!(enqueued, dequeued);
+    }
+
+    fn batch<R: Reclaimer>() {
+        let queue: LPRQ<i32, R> = LPRQ::new(8);
+        let mut handle = R::handle();
+        queue.enqueue(0, &mut handle).unwrap();
+        // Spans several PRQs
+        queue.enqueue_batch(1..35, &mut handle).unwrap();
+        queue.enqueue(35, &mut handle).unwrap();
+
+        let mut out = vec![];
+        while out.len() < 36 {
+            let count = queue.dequeue_batch(&mut out, 8, &mut handle).unwrap();
+            assert!(count > 0 && count <= 8);
+        }
+        assert_eq!(out, (0..36).collect::<Vec<_>>());
+        assert_eq!(
+            queue.dequeue_batch(&mut out, 8, &mut handle),
+            Err(TryDequeueError::Empty)
+        );
+
+        queue.close(&mut handle);
+        assert_eq!(
+            queue.enqueue_batch(vec![1, 2], &mut handle),
+            Err(TryEnqueueError::Closed(vec![1, 2]))
+        );
+        assert_eq!(
+            queue.dequeue_batch(&mut out, 8, &mut handle),
+            Err(TryDequeueError::Closed)
+        );
+    }
+
+    fn batch_concurrent<R: Reclaimer>() {
+        let queue: Arc<LPRQ<usize, R>> = Arc::new(LPRQ::new(8));
+
+        let mut handles = vec![];
+
+        for i in 0..4 {
+            let queue = Arc::clone(&queue);
+            let handle = thread::spawn(move || {
+                let mut handle = R::handle();
+                for j in 0..10 {
+                    let start = i * 100 + j * 10;
+                    queue.enqueue_batch(start..start + 10, &mut handle).unwrap();
+                }
+            });
+            handles.push(handle);
+        }
+
+        let mut handle = R::handle();
+        let mut out = vec![];
+        while out.len() < 400 {
+            let _ = queue.dequeue_batch(&mut out, 16, &mut handle);
+        }
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let dequeue_sum: usize = out.iter().sum();
+        assert_eq!(dequeue_sum, 400 * 399 / 2, "Sums do not match!");
+    }
+
+    fn len<R: Reclaimer>() {
+        let queue: LPRQ<i32, R> = LPRQ::new(8);
+        let mut handle = R::handle();
+        assert_eq!(queue.len(&mut handle), 0);
+        for i in 0..5 {
+            queue.enqueue(i, &mut handle).unwrap();
+        }
+        assert_eq!(queue.len(&mut handle), 5);
+        // Tickets burned by the enqueue that found a segment full are counted as items
+        for i in 5..25 {
+            queue.enqueue(i, &mut handle).unwrap();
+        }
+        assert!((25..=27).contains(&queue.len(&mut handle)));
+        for _ in 0..15 {
+            queue.dequeue(&mut handle).unwrap();
+        }
+        assert!((10..=12).contains(&queue.len(&mut handle)));
+        queue.close(&mut handle);
+        while queue.dequeue(&mut handle).is_ok() {}
+        assert_eq!(queue.len(&mut handle), 0);
+        assert!(queue.is_empty(&mut handle));
+    }
+
+    fn ring_sizes<R: Reclaimer>() {
+        for ring_size in [1, 2, 64] {
+            let queue: LPRQ<i32, R> = LPRQ::new(ring_size);
+            let mut handle = R::handle();
+            for i in 0..100 {
+                queue.enqueue(i, &mut handle).unwrap();
+            }
+            queue.enqueue_batch(100..200, &mut handle).unwrap();
+            for i in 0..200 {
+                assert_eq!(queue.dequeue(&mut handle), Ok(i));
+            }
+            assert_eq!(queue.dequeue(&mut handle), Err(TryDequeueError::Empty));
+        }
+    }
+
+    fn remapped_concurrent<R: Reclaimer>() {
+        let queue: Arc<LPRQ<usize, R, Remapped>> = Arc::new(LPRQ::new(64));
+
+        let mut producers = vec![];
+        for i in 0..4 {
+            let queue = Arc::clone(&queue);
+            producers.push(thread::spawn(move || {
+                let mut handle = R::handle();
+                for j in 0..100 {
+                    queue.enqueue(i * 100 + j, &mut handle).unwrap();
+                }
+            }));
+        }
+
+        let mut consumers = vec![];
+        for _ in 0..4 {
+            let queue = Arc::clone(&queue);
+            consumers.push(thread::spawn(move || {
+                let mut handle = R::handle();
+                let mut sum = 0;
+                for _ in 0..100 {
+                    loop {
+                        if let Ok(v) = queue.dequeue(&mut handle) {
+                            sum += v;
+                            break;
+                        }
+                    }
+                }
+                sum
+            }));
+        }
+
+        for handle in producers {
+            handle.join().unwrap();
+        }
+        let sum: usize = consumers.into_iter().map(|h| h.join().unwrap()).sum();
+        assert_eq!(sum, 400 * 399 / 2, "Sums do not match!");
+    }
+
+    fn segment_pool<R: Reclaimer>() {
+        let queue: LPRQ<i32, R> = LPRQ::with_segment_pool(8, 4);
+        let mut handle = R::handle();
+        for _ in 0..5 {
+            for i in 0..30 {
+                queue.enqueue(i, &mut handle).unwrap();
+            }
+            for i in 0..30 {
+                assert_eq!(queue.dequeue(&mut handle), Ok(i));
+            }
+        }
+        let stats = queue.pool_stats().unwrap();
+        let tail = unsafe { &*R::load_ptr(&queue.tail) };
+        // Every segment was either taken from the pool or allocated
+        assert_eq!(stats.hits + stats.misses, tail.index + 1);
+    }
+
+    fn segment_pool_concurrent<R: Reclaimer>() {
+        let queue: Arc<LPRQ<usize, R>> = Arc::new(LPRQ::with_segment_pool(8, 2));
+
+        let mut producers = vec![];
+        for i in 0..4 {
+            let queue = Arc::clone(&queue);
+            producers.push(thread::spawn(move || {
+                let mut handle = R::handle();
+                for j in 0..100 {
+                    queue.enqueue(i * 100 + j, &mut handle).unwrap();
+                }
+            }));
+        }
+
+        let mut consumers = vec![];
+        for _ in 0..4 {
+            let queue = Arc::clone(&queue);
+            consumers.push(thread::spawn(move || {
+                let mut handle = R::handle();
+                let mut sum = 0;
+                for _ in 0..100 {
+                    loop {
+                        if let Ok(v) = queue.dequeue(&mut handle) {
+                            sum += v;
+                            break;
+                        }
+                    }
+                }
+                sum
+            }));
+        }
+
+        for handle in producers {
+            handle.join().unwrap();
+        }
+        let sum: usize = consumers.into_iter().map(|h| h.join().unwrap()).sum();
+        assert_eq!(sum, 400 * 399 / 2, "Sums do not match!");
+        drop(queue);
+        Domain::global().eager_reclaim();
+    }
+
+    #[test]
+    fn segment_pool_reuses_reclaimed_segments() {
+        let queue: LPRQ<i32, HazardPointers> = LPRQ::with_segment_pool(8, 4);
+        let mut handle = HazardPointers::handle();
+        for _ in 0..5 {
+            for i in 0..30 {
+                queue.enqueue(i, &mut handle).unwrap();
+            }
+            for i in 0..30 {
+                assert_eq!(queue.dequeue(&mut handle), Ok(i));
+            }
+            // Hand the drained segments back to the pool
+            Domain::global().eager_reclaim();
+        }
+        let stats = queue.pool_stats().unwrap();
+        assert!(stats.hits >= 8, "{stats:?}");
+        assert_eq!(stats.discarded, 0);
+    }
 }
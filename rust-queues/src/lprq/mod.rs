@@ -1,5 +1,15 @@
 pub mod arc_lprq;
+pub mod bounded_prq;
+pub mod channel;
 pub mod epoch_lprq;
+pub mod ibr_lprq;
+pub mod layout;
 pub mod leak_lprq;
+// Holds the queue the reclaimer variants alias
+#[allow(clippy::module_inception)]
 pub mod lprq;
+pub mod pool;
 mod prq;
+
+#[cfg(all(test, loom))]
+mod loom_tests;
@@ -0,0 +1,103 @@
+use std::{ptr::null_mut, sync::atomic::Ordering::SeqCst};
+
+use crate::{
+    reclaim::{Reclaimer, Recycle},
+    sync::atomic::{AtomicPtr, AtomicUsize},
+};
+
+use super::{layout::Layout, prq::PRQ};
+
+// Reclaimed PRQ segments kept around so LPRQ can reuse them instead of allocating a new ring every
+// time the tail fills up. The free-list is an array of slots rather than a linked stack, a segment
+// is taken out with a single swap so there is no ABA problem, and its length bounds the pool
+
+/// Counters of the segment pool of an LPRQ.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PoolStats {
+    /// New segments that were taken from the pool
+    pub hits: usize,
+    /// New segments that had to be allocated because the pool was empty
+    pub misses: usize,
+    /// Reclaimed segments that were freed because the pool was full
+    pub discarded: usize,
+}
+
+pub(crate) struct SegmentPool<T: 'static, R: Reclaimer, L: Layout> {
+    slots: Box<[AtomicPtr<PRQ<T, R, L>>]>,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+    discarded: AtomicUsize,
+}
+
+impl<T, R: Reclaimer, L: Layout> Drop for SegmentPool<T, R, L> {
+    fn drop(&mut self) {
+        for slot in self.slots.iter() {
+            let segment = slot.load(SeqCst);
+            if !segment.is_null() {
+                // Safety: Segments in the pool are owned by it
+                unsafe { R::dealloc(segment) };
+            }
+        }
+    }
+}
+
+impl<T, R: Reclaimer, L: Layout> SegmentPool<T, R, L> {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            slots: (0..capacity).map(|_| AtomicPtr::new(null_mut())).collect(),
+            hits: AtomicUsize::new(0),
+            misses: AtomicUsize::new(0),
+            discarded: AtomicUsize::new(0),
+        }
+    }
+
+    // Takes a segment out of the pool, it is owned by the caller and still has to be reset
+    pub(crate) fn take(&self) -> Option<*mut PRQ<T, R, L>> {
+        for slot in self.slots.iter() {
+            if !slot.load(SeqCst).is_null() {
+                let segment = slot.swap(null_mut(), SeqCst);
+                if !segment.is_null() {
+                    self.hits.fetch_add(1, SeqCst);
+                    return Some(segment);
+                }
+            }
+        }
+        self.misses.fetch_add(1, SeqCst);
+        None
+    }
+
+    // Hands a segment owned by the caller to the pool, or frees it if the pool is full
+    //
+    // Safety: The segment must come from R::alloc and can not be reachable by anyone else
+    pub(crate) unsafe fn put(&self, segment: *mut PRQ<T, R, L>) {
+        for slot in self.slots.iter() {
+            if slot
+                .compare_exchange(null_mut(), segment, SeqCst, SeqCst)
+                .is_ok()
+            {
+                return;
+            }
+        }
+        self.discarded.fetch_add(1, SeqCst);
+        R::dealloc(segment);
+    }
+
+    pub(crate) fn stats(&self) -> PoolStats {
+        PoolStats {
+            hits: self.hits.load(SeqCst),
+            misses: self.misses.load(SeqCst),
+            discarded: self.discarded.load(SeqCst),
+        }
+    }
+}
+
+impl<T, R: Reclaimer, L: Layout> Recycle for PRQ<T, R, L> {
+    unsafe fn recycle(ptr: *mut Self) {
+        // The segment holds on to its pool, so the pool outlives every segment that can still be
+        // reclaimed into it
+        match (*ptr).pool.take() {
+            Some(pool) => pool.put(ptr),
+            None => R::dealloc(ptr),
+        }
+    }
+}
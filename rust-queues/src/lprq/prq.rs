@@ -1,16 +1,30 @@
 use crossbeam_utils::CachePadded;
-use std::{
-    array,
-    fmt::Debug,
-    ptr::{self, null_mut},
-    sync::atomic::{AtomicPtr, AtomicUsize, Ordering},
-    thread,
+use std::{fmt::Debug, mem, ptr, sync::atomic::Ordering};
+
+use crate::{
+    reclaim::{HazardPointers, Reclaimer},
+    shared_queue::{TryDequeueError, TryEnqueueError},
+    sync::{
+        atomic::{AtomicPtr, AtomicUsize},
+        spin_loop, thread_local, Arc,
+    },
 };
 
-use haphazard;
+use super::{
+    layout::{Layout, Padded, Ring},
+    pool::SegmentPool,
+};
+
+// Ids for the thread tokens, handed out once per thread and never reused. They only have to be
+// unique among the threads using a PRQ, and a usize counter leaves the token bit alone long before
+// it could wrap
+static NEXT_THREAD_ID: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(1);
+
+thread_local! {
+    static THREAD_ID: usize = NEXT_THREAD_ID.fetch_add(1, Ordering::Relaxed);
+}
 
-// Make sure cells are on different cache lines
-#[repr(align(128))]
+// Whether cells share cache lines is up to the layout of the ring
 struct Cell<T> {
     safe_and_epoch: AtomicUsize,
     // NOTE: This is a std::sync AtomicPtr and not the haphazard one
@@ -66,8 +80,8 @@ impl<T> Cell<T> {
     }
 
     fn make_token(thread_id: usize) -> *mut T {
-        let tagged = thread_id & Self::TOKEN_MASK;
-        ptr::invalid_mut(tagged)
+        let tagged = thread_id | Self::TOKEN_MASK;
+        ptr::without_provenance_mut(tagged)
     }
 
     // Block of utility functions for bitmasking that should all be inlined
@@ -87,178 +101,359 @@ impl<T> Cell<T> {
     }
 }
 
-#[derive(Debug)]
-pub struct PRQ<T, const N: usize> {
+#[allow(clippy::upper_case_acronyms)] // Named as in the paper
+pub struct PRQ<T: 'static, R: Reclaimer = HazardPointers, L: Layout = Padded> {
     head: CachePadded<AtomicUsize>,
     tail: CachePadded<AtomicUsize>, // Top bit here is set if the queue is closed
     //closed: CachePadded<AtomicBool>,
-    array: [Cell<T>; N],
-    pub next: CachePadded<haphazard::AtomicPtr<PRQ<T, N>>>,
+    // The ring size is a power of two, tickets are masked and remapped to get the cell
+    array: Ring<L::Slot<Cell<T>>>,
+    shift: u32,       // log2 of the ring size, tickets are shifted by it to get the cycle
+    finalized: bool,  // Set for the closed end of a closed LPRQ
+    pub index: usize, // Position of the segment in its LPRQ, used to estimate the length
+    pub next: CachePadded<R::Atomic<PRQ<T, R, L>>>,
+    pub pool: Option<Arc<SegmentPool<T, R, L>>>, // Where the segment goes once it is reclaimed
 }
 
-impl<T, const N: usize> PRQ<T, N> {
-    pub fn new() -> Self {
+impl<T: 'static, R: Reclaimer, L: Layout> PRQ<T, R, L> {
+    // A ring of size cells, which must be a power of two
+    pub fn new(size: usize) -> Self {
+        assert!(
+            size.is_power_of_two(),
+            "The ring size must be a power of two"
+        );
+        PRQ {
+            head: AtomicUsize::new(size).into(),
+            array: Ring::new(size),
+            shift: size.trailing_zeros(),
+            tail: AtomicUsize::new(size).into(),
+            next: R::null().into(),
+            finalized: false,
+            index: 0,
+            pool: None,
+        }
+    }
+
+    // An empty PRQ that is closed from the start. It is linked in as the last segment when an
+    // LPRQ is closed, so nothing is ever enqueued in it or linked after it. A single cell is enough
+    pub fn new_finalized() -> Self {
         PRQ {
-            head: AtomicUsize::new(N).into(),
-            array: array::from_fn(|_| Default::default()),
-            tail: AtomicUsize::new(N).into(),
-            next: unsafe { haphazard::AtomicPtr::new(null_mut()).into() },
-        }
-    }
-
-    pub fn new_with_item(value_ptr: *const T) -> Self {
-        let prq = PRQ {
-            head: AtomicUsize::new(N).into(),
-            tail: AtomicUsize::new(N).into(),
-            array: array::from_fn(|_| Default::default()),
-            next: unsafe { haphazard::AtomicPtr::new(null_mut()).into() },
-        };
-        prq
-            .enqueue(value_ptr)
-            .expect("Failed to enqueue an item in a new and empty PRQ, Should not happen ever");
-        prq
-    }
-
-    // Returns Ok() if enqueue was succesfull, Err() if the queue is closed
-    pub fn enqueue(&self, value_ptr: *const T) -> Result<(), ()> {
-        // Get a unique thread token
-        let thread_id: usize = thread::current().id().as_u64().get().try_into().unwrap();
-        let thread_token = Cell::<T>::make_token(thread_id);
+            head: AtomicUsize::new(1).into(),
+            array: Ring::new(1),
+            shift: 0,
+            tail: AtomicUsize::new(1 | (1 << 63)).into(),
+            next: R::null().into(),
+            finalized: true,
+            index: 0,
+            pool: None,
+        }
+    }
+
+    // Puts a segment that nobody else can see anymore back in the state of a new one, so it can
+    // be linked in again. Only the values are left behind, they are owned by whoever dequeued them
+    pub fn reset(&mut self) {
+        self.head.store(self.size(), Ordering::SeqCst);
+        self.tail.store(self.size(), Ordering::SeqCst);
+        for cell in self.array.iter_mut() {
+            *cell = Default::default();
+        }
+        *self.next = R::null();
+        self.finalized = false;
+        self.index = 0;
+    }
+
+    // Returns Ok() if enqueue was succesfull, Err() with the value if the queue is full or closed
+    pub fn enqueue(&self, value_ptr: *const T) -> Result<(), TryEnqueueError<*const T>> {
+        let thread_token = Self::thread_token();
         loop {
             let tail_ticket: usize = self.tail.fetch_add(1, Ordering::SeqCst);
             let tail_val: usize = (!(1 << 63)) & tail_ticket;
             let closed = tail_ticket & (1 << 63) != 0;
             if closed {
-                return Err(());
+                return Err(TryEnqueueError::Closed(value_ptr));
             }
-            let cycle = tail_val / N;
-            let index = tail_val % N;
 
-            let cell = &self.array[index];
+            if self.enqueue_ticket(tail_val, value_ptr, thread_token) {
+                return Ok(());
+            }
 
-            let (safe, epoch) = cell.load_safe_and_epoch(Ordering::SeqCst);
-            let value = cell.value.load(Ordering::SeqCst);
+            // Check if the queue is full
+            if tail_val >= self.head.load(Ordering::SeqCst) + self.size() {
+                // Set the top bit of the tail to indicate that the queue is closed
+                self.tail.fetch_or(1 << 63, Ordering::SeqCst);
+                return Err(TryEnqueueError::Full(value_ptr));
+            }
+        }
+    }
 
-            if value.is_null()
-                && epoch <= cycle
-                && (safe || self.head.load(Ordering::SeqCst) <= cycle)
+    // Enqueues the values in order, reserving the tickets for all of them with a single FAA.
+    // Returns Err() with the values that were not enqueued if the queue is full or closed
+    pub fn enqueue_batch<'a>(
+        &self,
+        values: &'a [*const T],
+    ) -> Result<(), TryEnqueueError<&'a [*const T]>> {
+        let thread_token = Self::thread_token();
+        let mut rest = values;
+        while !rest.is_empty() {
+            let count = rest.len();
+            let tail_ticket: usize = self.tail.fetch_add(count, Ordering::SeqCst);
+            let start: usize = (!(1 << 63)) & tail_ticket;
+            let closed = tail_ticket & (1 << 63) != 0;
+            if closed {
+                return Err(TryEnqueueError::Closed(rest));
+            }
+
+            // A ticket whose cell can not be used is skipped and the value moves on to the next
+            // one, so the values keep their order. Whatever is left gets a new range of tickets
+            for tail_val in start..start + count {
+                if self.enqueue_ticket(tail_val, rest[0], thread_token) {
+                    rest = &rest[1..];
+                    if rest.is_empty() {
+                        return Ok(());
+                    }
+                } else if tail_val >= self.head.load(Ordering::SeqCst) + self.size() {
+                    self.tail.fetch_or(1 << 63, Ordering::SeqCst);
+                    return Err(TryEnqueueError::Full(rest));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Get a unique thread token
+    fn thread_token() -> *mut T {
+        Cell::<T>::make_token(THREAD_ID.with(|id| *id))
+    }
+
+    // Tries to store the value in the cell of an already reserved ticket, returns false if the
+    // cell could not be used. The `if let Ok(_)` CAS chains here and below follow the paper's
+    // pseudocode
+    #[allow(clippy::redundant_pattern_matching)]
+    fn enqueue_ticket(&self, tail_val: usize, value_ptr: *const T, thread_token: *mut T) -> bool {
+        let cycle = tail_val >> self.shift;
+        let cell = self.cell(tail_val);
+
+        let (safe, epoch) = cell.load_safe_and_epoch(Ordering::SeqCst);
+        let value = cell.value.load(Ordering::SeqCst);
+
+        if value.is_null()
+            && epoch < cycle
+            && (safe || self.head.load(Ordering::SeqCst) <= tail_val)
+        {
+            if let Ok(_) =
+                cell.value
+                    .compare_exchange(value, thread_token, Ordering::SeqCst, Ordering::SeqCst)
             {
-                if let Ok(_) = cell.value.compare_exchange(
-                    value,
-                    thread_token,
+                if let Ok(_) = cell.compare_exchange_safe_and_epoch(
+                    (safe, epoch),
+                    (true, cycle),
                     Ordering::SeqCst,
                     Ordering::SeqCst,
                 ) {
-                    if let Ok(_) = cell.compare_exchange_safe_and_epoch(
-                        (safe, epoch),
-                        (true, cycle),
+                    if let Ok(_) = cell.value.compare_exchange(
+                        thread_token,
+                        value_ptr.cast_mut(),
                         Ordering::SeqCst,
                         Ordering::SeqCst,
                     ) {
-                        if let Ok(_) = cell.value.compare_exchange(
-                            thread_token,
-                            value_ptr.cast_mut(),
-                            Ordering::SeqCst,
-                            Ordering::SeqCst,
-                        ) {
-                            return Ok(());
-                        }
-                    } else {
-                        let _ = cell.value.compare_exchange(
-                            thread_token,
-                            ptr::null_mut(),
-                            Ordering::SeqCst,
-                            Ordering::SeqCst,
-                        );
+                        return true;
                     }
+                } else {
+                    let _ = cell.value.compare_exchange(
+                        thread_token,
+                        ptr::null_mut(),
+                        Ordering::SeqCst,
+                        Ordering::SeqCst,
+                    );
                 }
             }
+        }
+        false
+    }
 
-            // Check if the queue is full
-            if tail_val >= self.head.load(Ordering::SeqCst) + N {
-                // Set the top bit of the tail to indicate that the queue is closed
-                self.tail.fetch_or(1 << 63, Ordering::SeqCst);
-                return Err(());
+    pub fn dequeue(&self) -> Result<*mut T, TryDequeueError> {
+        loop {
+            let head_val = self.head.fetch_add(1, Ordering::SeqCst);
+            if let Some(value) = self.dequeue_ticket(head_val) {
+                return Ok(value);
             }
+            // Is the queue empty?
+            self.check_empty(head_val + 1)?;
         }
     }
 
-    pub fn dequeue(&self) -> Option<*mut T> {
+    // Dequeues up to max values into out, reserving the tickets with a single FAA. Returns how
+    // many values were dequeued, or Err() if there were none because the queue is empty
+    pub fn dequeue_batch(
+        &self,
+        out: &mut Vec<*mut T>,
+        max: usize,
+    ) -> Result<usize, TryDequeueError> {
+        if max == 0 {
+            return Ok(0);
+        }
         loop {
-            let head_val = self.head.fetch_add(1, Ordering::SeqCst);
-            let index = head_val % N;
-            let cycle = head_val / N;
-            let cell = &self.array[index];
-
-            let mut r: u64 = 0;
-            let mut tail = 0;
-            let mut closed = false;
-            loop {
-                // Update cell state
-                let (safe, epoch) = cell.load_safe_and_epoch(Ordering::SeqCst);
-                let value = cell.value.load(Ordering::SeqCst);
-
-                if epoch > head_val + N {
-                    break;
+            // Only reserve as many tickets as there seem to be items, tickets past the tail just
+            // get burned and make enqueuers retry
+            let tail_val = (!(1 << 63)) & self.tail.load(Ordering::SeqCst);
+            let head = self.head.load(Ordering::SeqCst);
+            let count = tail_val.saturating_sub(head).clamp(1, max);
+
+            let head_val = self.head.fetch_add(count, Ordering::SeqCst);
+            let before = out.len();
+            for ticket in head_val..head_val + count {
+                if let Some(value) = self.dequeue_ticket(ticket) {
+                    out.push(value);
                 }
+            }
+            if out.len() > before {
+                return Ok(out.len() - before);
+            }
+            self.check_empty(head_val + count)?;
+        }
+    }
 
-                if (!value.is_null()) && (!Cell::<T>::is_token(value.addr())) {
-                    if epoch == cycle {
-                        cell.value.store(ptr::null_mut(), Ordering::SeqCst);
-                        return Some(value);
-                    }
-                    if !safe {
-                        let new: (bool, usize) = cell.load_safe_and_epoch(Ordering::SeqCst);
-                        if new == (safe, epoch) {
-                            break;
-                        }
-
-                        if let Ok(_) = cell.compare_exchange_safe_and_epoch(
-                            (safe, epoch),
-                            (false, epoch),
-                            Ordering::SeqCst,
-                            Ordering::SeqCst,
-                        ) {
-                            break;
-                        }
+    // Takes the value out of the cell of an already reserved ticket, returns None if the ticket
+    // has no value, the cell is then left so that no enqueuer can use it for this ticket
+    #[allow(clippy::redundant_pattern_matching)]
+    fn dequeue_ticket(&self, head_val: usize) -> Option<*mut T> {
+        let cycle = head_val >> self.shift;
+        let cell = self.cell(head_val);
+
+        let mut r: u64 = 0;
+        let mut tail = 0;
+        let mut closed = false;
+        loop {
+            // Update cell state
+            let (safe, epoch) = cell.load_safe_and_epoch(Ordering::SeqCst);
+            let value = cell.value.load(Ordering::SeqCst);
+
+            if epoch > cycle {
+                return None;
+            }
+
+            if (!value.is_null()) && (!Cell::<T>::is_token(value.addr())) {
+                if epoch == cycle {
+                    cell.value.store(ptr::null_mut(), Ordering::SeqCst);
+                    return Some(value);
+                }
+                // The value belongs to an earlier cycle whose dequeuer has not arrived yet,
+                // mark the cell unsafe so no enqueuer reuses it until that value is taken
+                if !safe {
+                    let new: (bool, usize) = cell.load_safe_and_epoch(Ordering::SeqCst);
+                    if new == (safe, epoch) {
+                        return None;
                     }
+                } else if let Ok(_) = cell.compare_exchange_safe_and_epoch(
+                    (safe, epoch),
+                    (false, epoch),
+                    Ordering::SeqCst,
+                    Ordering::SeqCst,
+                ) {
+                    return None;
                 }
+            } else {
                 if (r % 255) == 0 {
                     let tail_ticket = self.tail.load(Ordering::SeqCst);
                     tail = tail_ticket & (!(1 << 63));
                     closed = tail_ticket & (1 << 63) != 0;
                 }
 
-                if !safe || tail < head_val + 1 || closed || r > (4 * N).try_into().unwrap() {
-                    if Cell::<T>::is_token(value.addr()) {
-                        if let Ok(_) = cell.value.compare_exchange(
-                            value,
-                            ptr::null_mut(),
-                            Ordering::SeqCst,
-                            Ordering::SeqCst,
-                        ) {
-                            continue;
-                        }
+                if !safe
+                    || tail < head_val + 1
+                    || closed
+                    || r > (4 * self.size()).try_into().unwrap()
+                {
+                    // Kick out an enqueuer that has reserved the cell but not yet written it
+                    if Cell::<T>::is_token(value.addr())
+                        && cell
+                            .value
+                            .compare_exchange(
+                                value,
+                                ptr::null_mut(),
+                                Ordering::SeqCst,
+                                Ordering::SeqCst,
+                            )
+                            .is_err()
+                    {
+                        continue;
                     }
+                    // Move the cell past our cycle so no enqueuer can use it for this ticket
                     if let Ok(_) = cell.compare_exchange_safe_and_epoch(
                         (safe, epoch),
-                        (false, head_val),
+                        (safe, cycle),
                         Ordering::SeqCst,
                         Ordering::SeqCst,
                     ) {
-                        break;
+                        return None;
                     }
                 }
-                r = r + 1;
+                // Give the enqueuer that reserved the cell a moment to write it
+                r += 1;
+                spin_loop();
             }
-            // Is the queue empty?
+        }
+    }
+
+    // Returns Err() if the tail is not past head_end, i.e. there is nothing left to dequeue
+    fn check_empty(&self, head_end: usize) -> Result<(), TryDequeueError> {
+        let tail_ticket = self.tail.load(Ordering::SeqCst);
+        if ((!(1 << 63)) & tail_ticket) <= head_end {
+            self.fix_state();
+            if tail_ticket & (1 << 63) != 0 {
+                return Err(TryDequeueError::Closed);
+            }
+            return Err(TryDequeueError::Empty);
+        }
+        Ok(())
+    }
+    // Approximate number of items in the ring, never blocks
+    pub fn len(&self) -> usize {
+        let head = self.head.load(Ordering::SeqCst);
+        let tail = (!(1 << 63)) & self.tail.load(Ordering::SeqCst);
+        tail.saturating_sub(head).min(self.size())
+    }
+    // Number of cells in the ring
+    pub fn size(&self) -> usize {
+        self.array.len()
+    }
+    #[inline]
+    fn cell(&self, ticket: usize) -> &Cell<T> {
+        let index = ticket & (self.array.len() - 1);
+        &self.array[L::remap(index, self.shift, mem::size_of::<Cell<T>>())]
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    // Sets the closed bit, all enqueues after this fail
+    pub fn close(&self) {
+        self.tail.fetch_or(1 << 63, Ordering::SeqCst);
+    }
+    pub fn is_finalized(&self) -> bool {
+        self.finalized
+    }
+    // Clears the closed bit once the queue has been drained, i.e. a dequeue has seen it empty.
+    // Returns true if the queue is open afterwards
+    #[allow(clippy::redundant_pattern_matching)]
+    pub fn reopen(&self) -> bool {
+        loop {
             let tail_ticket = self.tail.load(Ordering::SeqCst);
-            if ((!(1 << 63)) & tail_ticket) <= head_val + 1 {
-                self.fix_state();
-                return None;
+            if tail_ticket & (1 << 63) == 0 {
+                return true;
+            }
+            let head = self.head.load(Ordering::SeqCst);
+            if head < ((!(1 << 63)) & tail_ticket) {
+                return false;
+            }
+            if let Ok(_) =
+                self.tail
+                    .compare_exchange(tail_ticket, head, Ordering::SeqCst, Ordering::SeqCst)
+            {
+                return true;
             }
         }
     }
+    #[allow(clippy::redundant_pattern_matching)]
     fn fix_state(&self) {
         loop {
             let tail_ticket = self.tail.load(Ordering::SeqCst);
@@ -266,7 +461,8 @@ impl<T, const N: usize> PRQ<T, N> {
             if tail_ticket != self.tail.load(Ordering::SeqCst) {
                 continue;
             }
-            if head > ((!(1 << 63)) & tail_ticket) {
+            // Never true for a closed queue, fixing it must not clear the closed bit
+            if head > tail_ticket {
                 if let Ok(_) = self.tail.compare_exchange(
                     tail_ticket,
                     head,
@@ -285,7 +481,13 @@ impl<T, const N: usize> PRQ<T, N> {
 #[cfg(test)]
 mod test {
     use super::{Cell, PRQ};
+    use crate::{
+        lprq::layout::Remapped,
+        reclaim::HazardPointers,
+        shared_queue::{TryDequeueError, TryEnqueueError},
+    };
     use std::{
+        ptr,
         sync::{atomic::Ordering, Arc},
         thread,
     };
@@ -297,32 +499,264 @@ mod test {
         assert!(safe);
         assert_eq!(epoch, 0)
     }
+    #[test]
+    fn thread_tokens() {
+        let token = PRQ::<i32>::thread_token();
+        assert!(Cell::<i32>::is_token(token.addr()));
+        assert_eq!(token, PRQ::<i32>::thread_token());
+        let other = thread::spawn(|| PRQ::<i32>::thread_token().addr())
+            .join()
+            .unwrap();
+        assert!(Cell::<i32>::is_token(other));
+        assert_ne!(token.addr(), other);
+        let value = Box::new(0);
+        assert!(!Cell::<i32>::is_token((&*value as *const i32).addr()));
+    }
+
     #[test]
     fn basic_prq() {
-        let prq: PRQ<i32, 5> = PRQ::new();
+        let prq: PRQ<i32> = PRQ::new(8);
         let tail_ticket = prq.tail.load(Ordering::SeqCst);
-        assert_eq!(false, tail_ticket & (1 << 63) != 0);
+        assert!(tail_ticket & (1 << 63) == 0);
 
-        for i in 0..5 {
+        for i in 0..8 {
             let item = Box::into_raw(Box::new(i));
             assert_eq!(prq.enqueue(item), Ok(()));
         }
         // PRQ is now full, should fail
-        let item = Box::into_raw(Box::new(5));
-        assert_eq!(prq.enqueue(item), Err(()));
+        let item = Box::into_raw(Box::new(8));
+        assert_eq!(
+            prq.enqueue(item),
+            Err(TryEnqueueError::Full(item.cast_const()))
+        );
         let _ = unsafe { Box::from_raw(item) };
 
-        for i in 0..5 {
+        for i in 0..8 {
+            let value = unsafe { Box::from_raw(prq.dequeue().unwrap()) };
+            assert_eq!(value, Box::new(i));
+        }
+        assert_eq!(prq.dequeue(), Err(TryDequeueError::Closed));
+    }
+
+    // Puts a cell in the state a racing thread could have left it in
+    fn set_cell(prq: &PRQ<i32>, index: usize, safe: bool, epoch: usize, value: *mut i32) {
+        let cell = &prq.array[index];
+        cell.safe_and_epoch
+            .store(Cell::<i32>::usize_from_sae((safe, epoch)), Ordering::SeqCst);
+        cell.value.store(value, Ordering::SeqCst);
+    }
+
+    #[test]
+    fn thread_tokens_are_tagged() {
+        // A reserved cell must not look like it holds a value
+        let token = Cell::<i32>::make_token(5);
+        assert!(Cell::<i32>::is_token(token.addr()));
+    }
+
+    #[test]
+    fn empty_dequeue_leaves_cell_usable() {
+        let prq: PRQ<i32> = PRQ::new(4);
+        assert_eq!(prq.dequeue(), Err(TryDequeueError::Empty));
+        // The dequeue moved cell 0 past its cycle, the next cycle must still be able to use it
+        for i in 0..4 {
+            assert_eq!(prq.enqueue(Box::into_raw(Box::new(i))), Ok(()));
+        }
+        for i in 0..4 {
+            let value = unsafe { Box::from_raw(prq.dequeue().unwrap()) };
+            assert_eq!(*value, i);
+        }
+    }
+
+    #[test]
+    fn enqueue_skips_cell_its_dequeuer_passed() {
+        let prq: PRQ<i32> = PRQ::new(4);
+        // The dequeuer of ticket 4 found cell 0 empty before its enqueuer got there
+        set_cell(&prq, 0, true, 1, ptr::null_mut());
+        prq.head.store(5, Ordering::SeqCst);
+        let item = Box::into_raw(Box::new(1));
+        assert_eq!(prq.enqueue(item), Ok(()));
+        assert_eq!(prq.dequeue(), Ok(item));
+        let _ = unsafe { Box::from_raw(item) };
+    }
+
+    #[test]
+    fn enqueue_uses_unsafe_cell_no_dequeuer_passed() {
+        let prq: PRQ<i32> = PRQ::new(4);
+        // Cell 0 was marked unsafe in an earlier cycle, but no dequeuer has reached ticket 4 yet
+        set_cell(&prq, 0, false, 0, ptr::null_mut());
+        let item = Box::into_raw(Box::new(1));
+        assert_eq!(prq.enqueue(item), Ok(()));
+        assert_eq!(prq.array[0].value.load(Ordering::SeqCst), item);
+        assert_eq!(prq.dequeue(), Ok(item));
+        let _ = unsafe { Box::from_raw(item) };
+    }
+
+    #[test]
+    fn later_dequeuer_keeps_earlier_value() {
+        let prq: PRQ<i32> = PRQ::new(4);
+        // The value of ticket 4 is still in cell 0 when the dequeuer of ticket 8 gets there
+        let item = Box::into_raw(Box::new(1));
+        set_cell(&prq, 0, true, 1, item);
+        prq.head.store(8, Ordering::SeqCst);
+        prq.tail.store(9, Ordering::SeqCst);
+        assert_eq!(prq.dequeue(), Err(TryDequeueError::Empty));
+        // The slow dequeuer of ticket 4 must still find it
+        prq.head.store(4, Ordering::SeqCst);
+        assert_eq!(prq.dequeue(), Ok(item));
+        let _ = unsafe { Box::from_raw(item) };
+    }
+
+    #[test]
+    fn earlier_dequeuer_keeps_later_value() {
+        let prq: PRQ<i32> = PRQ::new(4);
+        // Cell 0 already holds the value of ticket 8 when the dequeuer of ticket 4 gets there
+        let item = Box::into_raw(Box::new(1));
+        set_cell(&prq, 0, true, 2, item);
+        prq.tail.store(9, Ordering::SeqCst);
+        assert_eq!(prq.dequeue(), Ok(item));
+        let _ = unsafe { Box::from_raw(item) };
+    }
+
+    #[test]
+    fn remapped_prq() {
+        // Larger than a cache line of cells, so neighbouring tickets really are on other lines
+        let prq: PRQ<i32, HazardPointers, Remapped> = PRQ::new(64);
+        // The remapping assumes the ring starts on a line
+        assert_eq!(prq.array.as_ptr() as usize % 128, 0);
+        for round in 0..3 {
+            for i in 0..40 {
+                assert_eq!(prq.enqueue(Box::into_raw(Box::new(round * 40 + i))), Ok(()));
+            }
+            for i in 0..40 {
+                let value = unsafe { Box::from_raw(prq.dequeue().unwrap()) };
+                assert_eq!(*value, round * 40 + i);
+            }
+        }
+        assert_eq!(prq.dequeue(), Err(TryDequeueError::Empty));
+    }
+
+    #[test]
+    #[should_panic(expected = "power of two")]
+    fn ring_size_must_be_a_power_of_two() {
+        let _: PRQ<i32> = PRQ::new(10);
+    }
+
+    #[test]
+    fn prq_len() {
+        let prq: PRQ<i32> = PRQ::new(4);
+        assert!(prq.is_empty());
+        for i in 0..3 {
+            assert_eq!(prq.enqueue(Box::into_raw(Box::new(i))), Ok(()));
+        }
+        assert_eq!(prq.len(), 3);
+        drop(unsafe { Box::from_raw(prq.dequeue().unwrap()) });
+        assert_eq!(prq.len(), 2);
+        while let Ok(ptr) = prq.dequeue() {
+            drop(unsafe { Box::from_raw(ptr) });
+        }
+        assert!(prq.is_empty());
+    }
+
+    #[test]
+    fn prq_reuses_cells() {
+        let prq: PRQ<i32> = PRQ::new(4);
+        // Dequeueing from an empty ring advances the cells, they must still be usable afterwards
+        for i in 0..20 {
+            assert_eq!(prq.dequeue(), Err(TryDequeueError::Empty));
+            let item = Box::into_raw(Box::new(i));
+            assert_eq!(prq.enqueue(item), Ok(()));
             let value = unsafe { Box::from_raw(prq.dequeue().unwrap()) };
             assert_eq!(value, Box::new(i));
         }
-        assert_eq!(prq.dequeue(), None);
+    }
+
+    #[test]
+    fn reset_prq() {
+        let mut prq: PRQ<i32> = PRQ::new(4);
+        for i in 0..5 {
+            let item = Box::into_raw(Box::new(i));
+            if prq.enqueue(item).is_err() {
+                drop(unsafe { Box::from_raw(item) });
+            }
+        }
+        while let Ok(ptr) = prq.dequeue() {
+            drop(unsafe { Box::from_raw(ptr) });
+        }
+        // Full and drained, so closed for good until it is reset
+        assert_eq!(prq.dequeue(), Err(TryDequeueError::Closed));
+
+        prq.reset();
+        for i in 0..4 {
+            assert_eq!(prq.enqueue(Box::into_raw(Box::new(i))), Ok(()));
+        }
+        for i in 0..4 {
+            assert_eq!(unsafe { *Box::from_raw(prq.dequeue().unwrap()) }, i);
+        }
+    }
+
+    #[test]
+    fn batch_prq() {
+        let prq: PRQ<i32> = PRQ::new(8);
+        let items: Vec<*const i32> = (0..10)
+            .map(|i| Box::into_raw(Box::new(i)).cast_const())
+            .collect();
+        // Only 8 fit, the rest is handed back
+        assert_eq!(
+            prq.enqueue_batch(&items),
+            Err(TryEnqueueError::Full(&items[8..]))
+        );
+
+        let mut out = vec![];
+        assert_eq!(prq.dequeue_batch(&mut out, 3), Ok(3));
+        assert_eq!(prq.dequeue_batch(&mut out, 10), Ok(5));
+        assert_eq!(
+            prq.dequeue_batch(&mut out, 10),
+            Err(TryDequeueError::Closed)
+        );
+        for (i, ptr) in out.into_iter().enumerate() {
+            assert_eq!(unsafe { *Box::from_raw(ptr) }, i as i32);
+        }
+        for ptr in &items[8..] {
+            drop(unsafe { Box::from_raw(ptr.cast_mut()) });
+        }
+    }
+
+    #[test]
+    fn batch_prq_concurrent() {
+        const N: usize = 64;
+        let prq: Arc<PRQ<usize>> = Arc::new(PRQ::new(N));
+
+        let mut handles = vec![];
+
+        for i in 0..4 {
+            let queue = Arc::clone(&prq);
+            let handle = thread::spawn(move || {
+                let items: Vec<*const usize> = (0..10)
+                    .map(|j| Box::into_raw(Box::new(i * 10 + j)).cast_const())
+                    .collect();
+                queue.enqueue_batch(&items).is_ok()
+            });
+            handles.push(handle);
+        }
+
+        for handle in handles {
+            assert!(handle.join().unwrap());
+        }
+
+        let mut out = vec![];
+        while prq.dequeue_batch(&mut out, 7).is_ok() {}
+        assert_eq!(out.len(), 40);
+        let dequeue_sum: usize = out
+            .into_iter()
+            .map(|ptr| unsafe { *Box::from_raw(ptr) })
+            .sum();
+        assert_eq!(dequeue_sum, 40 * 39 / 2, "Sums do not match!");
     }
 
     #[test]
     fn prq_concurrent() {
-        const N: usize = 10;
-        let prq: Arc<PRQ<usize, N>> = Arc::new(PRQ::new());
+        const N: usize = 16;
+        let prq: Arc<PRQ<usize>> = Arc::new(PRQ::new(N));
 
         let mut handles = vec![];
 
@@ -330,7 +764,7 @@ mod test {
             let queue = Arc::clone(&prq);
             let handle = thread::spawn(move || {
                 let v = Box::into_raw(Box::new(i));
-                queue.enqueue(v)
+                queue.enqueue(v).is_ok()
             });
             handles.push(handle);
         }
@@ -342,7 +776,7 @@ mod test {
         }
 
         let mut dequeue_sum = 0;
-        while let Some(ptr) = prq.dequeue() {
+        while let Ok(ptr) = prq.dequeue() {
             let value = unsafe { Box::from_raw(ptr) };
             dequeue_sum += *value;
         }
@@ -0,0 +1,313 @@
+use std::{ptr, sync::Arc};
+
+use haphazard::{AtomicPtr, HazardPointer};
+
+use crossbeam_utils::CachePadded;
+
+use crate::shared_queue::{SharedQueue, TryDequeueError};
+
+use super::scq::SCQ;
+
+pub struct SharedLSCQ<'a, T, const N: usize> {
+    queue: Arc<LSCQ<T, N>>,
+    hazard1: HazardPointer<'a>,
+    hazard2: HazardPointer<'a>,
+}
+
+impl<'a, T, const N: usize> SharedLSCQ<'a, T, N> {
+    pub fn try_dequeue(&mut self) -> Result<T, TryDequeueError> {
+        self.queue.dequeue(&mut self.hazard1, &mut self.hazard2)
+    }
+}
+
+impl<'a, T, const N: usize> SharedQueue<T> for SharedLSCQ<'a, T, N> {
+    fn new() -> Self {
+        Self {
+            queue: Arc::new(LSCQ::new()),
+            hazard1: HazardPointer::new(),
+            hazard2: HazardPointer::new(),
+        }
+    }
+
+    fn enqueue(&mut self, val: T) {
+        self.queue.enqueue(val, &mut self.hazard1);
+    }
+
+    fn dequeue(&mut self) -> Option<T> {
+        self.try_dequeue().ok()
+    }
+}
+
+impl<'a, T, const N: usize> Clone for SharedLSCQ<'a, T, N> {
+    fn clone(&self) -> Self {
+        Self {
+            queue: self.queue.clone(),
+            hazard1: HazardPointer::new(),
+            hazard2: HazardPointer::new(),
+        }
+    }
+}
+
+#[allow(clippy::upper_case_acronyms)] // Named as in the paper
+pub(crate) struct LSCQ<T, const N: usize> {
+    head: CachePadded<AtomicPtr<SCQ<T, N>>>,
+    tail: CachePadded<AtomicPtr<SCQ<T, N>>>,
+}
+
+// The queue owns the boxed values stored in its segments, so it can only be shared between threads
+// if the values themselves can be sent between them
+unsafe impl<T: Send, const N: usize> Send for LSCQ<T, N> {}
+unsafe impl<T: Send, const N: usize> Sync for LSCQ<T, N> {}
+
+impl<T, const N: usize> Drop for LSCQ<T, N> {
+    fn drop(&mut self) {
+        // Empty the queue to drop any leftover items
+        let mut hazard1 = HazardPointer::new();
+        let mut hazard2 = HazardPointer::new();
+        while self.dequeue(&mut hazard1, &mut hazard2).is_ok() {}
+
+        let head = self.head.load_ptr();
+        let tail = self.tail.load_ptr();
+        if head == tail {
+            let old = unsafe { self.head.swap_ptr(ptr::null_mut()) }.expect("A LSCQ with both head and tail as null was dropped. This should never happen and indicates a bug or memory corruption");
+            unsafe { old.retire() };
+        } else {
+            panic!("Drop for LSCQ somehow failed to dequeue all its items")
+        }
+    }
+}
+
+impl<T, const N: usize> LSCQ<T, N> {
+    pub(crate) fn new() -> Self {
+        let initial: *mut SCQ<T, N> = Box::into_raw(Box::new(SCQ::new()));
+        Self {
+            head: unsafe { AtomicPtr::new(initial) }.into(),
+            tail: unsafe { AtomicPtr::new(initial) }.into(),
+        }
+    }
+    pub(crate) fn enqueue(&self, val: T, hazard: &mut HazardPointer) {
+        let val: *const T = Box::into_raw(Box::new(val));
+        loop {
+            // fast path: Add item to current SCQ
+            let queue = self.tail.safe_load(hazard).unwrap();
+            let queue_ptr: *const SCQ<T, N> = queue;
+            if queue.enqueue(val).is_ok() {
+                return;
+            }
+            // Slow path: Tail is full, allocate and add a new scq
+            let new_tail: AtomicPtr<SCQ<T, N>> = AtomicPtr::from(Box::new(SCQ::new_with_item(val)));
+            let new_tail_ptr = new_tail.load_ptr();
+            match unsafe {
+                queue
+                    .next
+                    .compare_exchange_ptr(ptr::null_mut(), new_tail_ptr)
+            } {
+                Ok(_) => {
+                    // Next successfully inserted, update tail to point to that
+                    let _ = unsafe {
+                        self.tail
+                            .compare_exchange_ptr(queue_ptr.cast_mut(), new_tail_ptr)
+                    };
+                    return;
+                }
+                Err(next) => {
+                    let _ = unsafe { self.tail.compare_exchange_ptr(queue_ptr.cast_mut(), next) };
+                    // Drop the failed new tail so it does not leak, the value stays ours
+                    let _ = unsafe { new_tail.retire() };
+                }
+            }
+        }
+    }
+    pub(crate) fn dequeue(
+        &self,
+        hazard1: &mut HazardPointer,
+        hazard2: &mut HazardPointer,
+    ) -> Result<T, TryDequeueError> {
+        loop {
+            let queue = self.head.safe_load(hazard1).unwrap();
+            if let Ok(v) = queue.dequeue() {
+                return Ok(*unsafe { Box::from_raw(v) });
+            }
+            // Failed, is this queue empty?
+            let Some(next_ptr) = hazard2.protect_ptr(unsafe { queue.next.as_std() }) else {
+                return Err(TryDequeueError::Empty);
+            };
+            // LSCQ is not empty, try to dequeue again. Items may have been enqueued right before
+            // the next SCQ was linked in, after the threshold had already run out
+            queue.reset_threshold();
+            if let Ok(v) = queue.dequeue() {
+                return Ok(*unsafe { Box::from_raw(v) });
+            }
+            // SCQ is empty, update head and restart
+            let queue_ptr: *const SCQ<T, N> = queue;
+            if let Ok(Some(old)) = unsafe {
+                self.head
+                    .compare_exchange_ptr(queue_ptr.cast_mut(), next_ptr.0.as_ptr())
+            } {
+                // The old SCQ is now empty, so we retire it
+                unsafe { old.retire() };
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{sync::Arc, thread};
+
+    use haphazard::{Domain, HazardPointer};
+
+    use super::LSCQ;
+
+    #[test]
+    fn basic() {
+        let queue: LSCQ<i32, 16> = LSCQ::new();
+        let mut hazard = HazardPointer::new();
+        for i in 0..100 {
+            queue.enqueue(i, &mut hazard);
+        }
+        let mut hazard2 = HazardPointer::new();
+        for i in 0..100 {
+            let v = queue.dequeue(&mut hazard, &mut hazard2).unwrap();
+            assert_eq!(v, i);
+        }
+    }
+
+    #[test]
+    fn basic_concurrent() {
+        let queue: Arc<LSCQ<i32, 16>> = Arc::new(LSCQ::new());
+
+        let mut handles = vec![];
+
+        for i in 0..10 {
+            let queue = Arc::clone(&queue);
+            let handle = thread::spawn(move || {
+                let mut hazard = HazardPointer::new();
+                for j in 0..10 {
+                    queue.enqueue(j + i, &mut hazard);
+                }
+            });
+            handles.push(handle);
+        }
+
+        for handle in handles {
+            let _ = handle.join();
+        }
+
+        handles = vec![];
+
+        for _i in 0..10 {
+            let queue = Arc::clone(&queue);
+            let handle = thread::spawn(move || {
+                let mut hazard1 = HazardPointer::new();
+                let mut hazard2 = HazardPointer::new();
+                for _j in 0..10 {
+                    queue.dequeue(&mut hazard1, &mut hazard2).unwrap();
+                }
+            });
+            handles.push(handle);
+        }
+        for handle in handles {
+            let _ = handle.join();
+        }
+        drop(queue);
+        Domain::global().eager_reclaim();
+    }
+    #[test]
+    fn dropping_with_non_empty() {
+        let queue: Arc<LSCQ<i32, 16>> = Arc::new(LSCQ::new());
+
+        let mut handles = vec![];
+
+        for i in 0..10 {
+            let queue = Arc::clone(&queue);
+            let handle = thread::spawn(move || {
+                let mut hazard = HazardPointer::new();
+                for j in 0..10 {
+                    queue.enqueue(j + i, &mut hazard);
+                }
+            });
+            handles.push(handle);
+        }
+
+        for handle in handles {
+            let _ = handle.join();
+        }
+
+        handles = vec![];
+
+        for _i in 0..10 {
+            let queue = Arc::clone(&queue);
+            let handle = thread::spawn(move || {
+                let mut hazard1 = HazardPointer::new();
+                let mut hazard2 = HazardPointer::new();
+                for _j in 0..5 {
+                    queue.dequeue(&mut hazard1, &mut hazard2).unwrap();
+                }
+            });
+            handles.push(handle);
+        }
+        for handle in handles {
+            let _ = handle.join();
+        }
+        drop(queue);
+        Domain::global().eager_reclaim();
+    }
+    #[test]
+    fn dropping_drops_leftover_items() {
+        let item = Arc::new(());
+        let queue: LSCQ<Arc<()>, 16> = LSCQ::new();
+        let mut hazard = HazardPointer::new();
+        for _ in 0..25 {
+            queue.enqueue(Arc::clone(&item), &mut hazard);
+        }
+        drop(hazard);
+        assert_eq!(Arc::strong_count(&item), 26);
+        drop(queue);
+        Domain::global().eager_reclaim();
+        assert_eq!(Arc::strong_count(&item), 1);
+    }
+
+    #[test]
+    fn mixed_concurrent() {
+        let queue: Arc<LSCQ<usize, 16>> = Arc::new(LSCQ::new());
+
+        let mut producers = vec![];
+        for i in 0..4 {
+            let queue = Arc::clone(&queue);
+            let handle = thread::spawn(move || {
+                let mut hazard = HazardPointer::new();
+                for j in 0..100 {
+                    queue.enqueue(i * 100 + j, &mut hazard);
+                }
+            });
+            producers.push(handle);
+        }
+
+        let mut consumers = vec![];
+        for _ in 0..4 {
+            let queue = Arc::clone(&queue);
+            let handle = thread::spawn(move || {
+                let mut hazard1 = HazardPointer::new();
+                let mut hazard2 = HazardPointer::new();
+                let mut sum = 0;
+                for _ in 0..100 {
+                    loop {
+                        if let Ok(v) = queue.dequeue(&mut hazard1, &mut hazard2) {
+                            sum += v;
+                            break;
+                        }
+                    }
+                }
+                sum
+            });
+            consumers.push(handle);
+        }
+
+        for handle in producers {
+            handle.join().unwrap();
+        }
+        let sum: usize = consumers.into_iter().map(|h| h.join().unwrap()).sum();
+        assert_eq!(sum, 400 * 399 / 2, "Sums do not match!");
+    }
+}
@@ -0,0 +1,366 @@
+use crossbeam_utils::CachePadded;
+use std::{
+    array,
+    ptr::null_mut,
+    sync::atomic::{AtomicIsize, AtomicPtr, AtomicUsize, Ordering},
+};
+
+use crate::shared_queue::{TryDequeueError, TryEnqueueError};
+
+use haphazard;
+
+// Top bit of the tail, set once the ring is closed
+const CLOSED_BIT: usize = 1 << 63;
+
+// log2 of the number of entries that fit in a cache line, consecutive indices are spread over
+// that many cache lines so they do not contend
+const MIN_ORDER: u32 = 4;
+
+// Compares two ring counters, allowing for them to wrap around
+macro_rules! cmp {
+    ($x:expr, $op:tt, $y:expr) => {
+        (($x).wrapping_sub($y) as isize) $op 0
+    };
+}
+
+// The index ring of SCQ (lfring in the reference implementation). Holds up to N indices below N,
+// in 2N entries that each pack a cycle, an unsafe bit and an index
+struct Ring<const N: usize> {
+    head: CachePadded<AtomicUsize>,
+    threshold: CachePadded<AtomicIsize>,
+    tail: CachePadded<AtomicUsize>, // Top bit here is set if the ring is closed
+    // 2N entries, stable const generics can not express an array of length 2 * N
+    array: [[AtomicUsize; 2]; N],
+}
+
+impl<const N: usize> Ring<N> {
+    const ORDER: u32 = N.trailing_zeros();
+    // Number of failed dequeues after which the ring is considered empty
+    const THRESHOLD: isize = (3 * N - 1) as isize;
+
+    fn new_empty() -> Self {
+        Ring {
+            head: AtomicUsize::new(0).into(),
+            threshold: AtomicIsize::new(-1).into(),
+            tail: AtomicUsize::new(0).into(),
+            array: array::from_fn(|_| [AtomicUsize::new(usize::MAX), AtomicUsize::new(usize::MAX)]),
+        }
+    }
+
+    // A ring holding every index from 0 to N - 1
+    fn new_full() -> Self {
+        let ring = Ring::new_empty();
+        for i in 0..N {
+            ring.entry(i)
+                .store(2 * N + Self::raw_map(i, Self::ORDER, N), Ordering::Relaxed);
+        }
+        ring.threshold.store(Self::THRESHOLD, Ordering::Relaxed);
+        ring.tail.store(N, Ordering::Relaxed);
+        ring
+    }
+
+    #[inline]
+    fn raw_map(index: usize, order: u32, n: usize) -> usize {
+        ((index & (n - 1)) >> (order - MIN_ORDER)) | ((index << MIN_ORDER) & (n - 1))
+    }
+
+    #[inline]
+    fn entry(&self, index: usize) -> &AtomicUsize {
+        let i = Self::raw_map(index, Self::ORDER + 1, 2 * N);
+        &self.array[i / 2][i % 2]
+    }
+
+    fn enqueue(&self, index: usize) -> bool {
+        let n = 2 * N;
+        let index = index ^ (n - 1);
+        loop {
+            let tail = self.tail.fetch_add(1, Ordering::SeqCst);
+            if tail & CLOSED_BIT != 0 {
+                return false;
+            }
+
+            let tail_cycle = (tail << 1) | (2 * n - 1);
+            let entry_ref = self.entry(tail);
+            let mut entry = entry_ref.load(Ordering::SeqCst);
+            loop {
+                let entry_cycle = entry | (2 * n - 1);
+                // The entry must be from an earlier cycle and either safe, or unsafe with no
+                // dequeuer that could still be waiting for it
+                if !(cmp!(entry_cycle, <, tail_cycle)
+                    && (entry == entry_cycle
+                        || (entry == (entry_cycle ^ n)
+                            && cmp!(self.head.load(Ordering::SeqCst), <=, tail))))
+                {
+                    break;
+                }
+                match entry_ref.compare_exchange_weak(
+                    entry,
+                    tail_cycle ^ index,
+                    Ordering::SeqCst,
+                    Ordering::SeqCst,
+                ) {
+                    Ok(_) => {
+                        if self.threshold.load(Ordering::SeqCst) != Self::THRESHOLD {
+                            self.threshold.store(Self::THRESHOLD, Ordering::SeqCst);
+                        }
+                        return true;
+                    }
+                    Err(current) => entry = current,
+                }
+            }
+        }
+    }
+
+    fn dequeue(&self) -> Option<usize> {
+        let n = 2 * N;
+        if self.threshold.load(Ordering::SeqCst) < 0 {
+            return None;
+        }
+
+        loop {
+            let head = self.head.fetch_add(1, Ordering::SeqCst);
+            let head_cycle = (head << 1) | (2 * n - 1);
+            let entry_ref = self.entry(head);
+            let mut attempt: usize = 0;
+            let mut tail = 0;
+            'again: loop {
+                let mut entry = entry_ref.load(Ordering::SeqCst);
+                loop {
+                    let entry_cycle = entry | (2 * n - 1);
+                    if entry_cycle == head_cycle {
+                        entry_ref.fetch_or(n - 1, Ordering::SeqCst);
+                        return Some(entry & (n - 1));
+                    }
+
+                    let entry_new = if (entry | n) != entry_cycle {
+                        // Holds an index of an earlier cycle, mark it unsafe
+                        let entry_new = entry & !n;
+                        if entry == entry_new {
+                            break 'again;
+                        }
+                        entry_new
+                    } else {
+                        // Empty, give a slow enqueuer a moment before moving the entry past our
+                        // cycle
+                        if (attempt % 256) == 0 {
+                            tail = self.tail.load(Ordering::SeqCst);
+                        }
+                        let closed = tail & CLOSED_BIT != 0;
+                        attempt += 1;
+                        if attempt <= 4 * 1024 && !closed && cmp!(tail & !CLOSED_BIT, >=, head + 1)
+                        {
+                            continue 'again;
+                        }
+                        head_cycle ^ ((!entry) & n)
+                    };
+
+                    if !cmp!(entry_cycle, <, head_cycle) {
+                        break 'again;
+                    }
+                    match entry_ref.compare_exchange_weak(
+                        entry,
+                        entry_new,
+                        Ordering::SeqCst,
+                        Ordering::SeqCst,
+                    ) {
+                        Ok(_) => break 'again,
+                        Err(current) => entry = current,
+                    }
+                }
+            }
+
+            // Is the ring empty?
+            let tail = self.tail.load(Ordering::SeqCst) & !CLOSED_BIT;
+            if cmp!(tail, <=, head + 1) {
+                self.catchup(tail, head + 1);
+                self.threshold.fetch_sub(1, Ordering::SeqCst);
+                return None;
+            }
+            if self.threshold.fetch_sub(1, Ordering::SeqCst) <= 0 {
+                return None;
+            }
+        }
+    }
+
+    // Moves the tail up to the head after dequeuers have overtaken it
+    fn catchup(&self, mut tail: usize, mut head: usize) {
+        while self
+            .tail
+            .compare_exchange_weak(tail, head, Ordering::SeqCst, Ordering::SeqCst)
+            .is_err()
+        {
+            head = self.head.load(Ordering::SeqCst);
+            tail = self.tail.load(Ordering::SeqCst);
+            // Unlike the reference this never reopens a closed ring, an LSCQ segment that has
+            // been closed must stay closed once the next one is linked in
+            if tail & CLOSED_BIT != 0 || cmp!(tail, >=, head) {
+                break;
+            }
+        }
+    }
+
+    fn reset_threshold(&self) {
+        self.threshold.store(Self::THRESHOLD, Ordering::SeqCst);
+    }
+
+    fn close(&self) {
+        self.tail.fetch_or(CLOSED_BIT, Ordering::SeqCst);
+    }
+}
+
+// A bounded SCQ segment. Values live in a plain array, the indices of the free slots are kept in
+// one ring (fq) and those of the taken slots in order in another (aq)
+#[allow(clippy::upper_case_acronyms)] // Named as in the paper
+pub struct SCQ<T, const N: usize> {
+    aq: Ring<N>,
+    fq: Ring<N>,
+    // NOTE: This is a std::sync AtomicPtr and not the haphazard one
+    values: [AtomicPtr<T>; N],
+    pub next: CachePadded<haphazard::AtomicPtr<SCQ<T, N>>>,
+}
+
+impl<T, const N: usize> SCQ<T, N> {
+    pub fn new() -> Self {
+        assert!(
+            N.is_power_of_two() && N >= 1 << MIN_ORDER,
+            "The ring size of a SCQ must be a power of two of at least 16"
+        );
+        SCQ {
+            aq: Ring::new_empty(),
+            fq: Ring::new_full(),
+            values: array::from_fn(|_| AtomicPtr::new(null_mut())),
+            next: unsafe { haphazard::AtomicPtr::new(null_mut()).into() },
+        }
+    }
+
+    pub fn new_with_item(value_ptr: *const T) -> Self {
+        let scq = SCQ::new();
+        scq.enqueue(value_ptr)
+            .expect("Failed to enqueue an item in a new and empty SCQ, Should not happen ever");
+        scq
+    }
+
+    // Returns Ok() if enqueue was succesfull, Err() with the value if the queue is full or closed
+    pub fn enqueue(&self, value_ptr: *const T) -> Result<(), TryEnqueueError<*const T>> {
+        let Some(index) = self.fq.dequeue() else {
+            // Full, close it so the LSCQ moves on to a new segment
+            self.aq.close();
+            return Err(TryEnqueueError::Full(value_ptr));
+        };
+        self.values[index].store(value_ptr.cast_mut(), Ordering::SeqCst);
+        if self.aq.enqueue(index) {
+            return Ok(());
+        }
+        // Closed in the meantime, give the slot back
+        self.fq.enqueue(index);
+        Err(TryEnqueueError::Closed(value_ptr))
+    }
+
+    pub fn dequeue(&self) -> Result<*mut T, TryDequeueError> {
+        let index = self.aq.dequeue().ok_or(TryDequeueError::Empty)?;
+        let value = self.values[index].load(Ordering::SeqCst);
+        self.fq.enqueue(index);
+        Ok(value)
+    }
+
+    // The threshold of aq has to be reset before dequeueing again once the next segment has been
+    // seen, otherwise a dequeue can miss items that were enqueued right before it was linked in
+    pub fn reset_threshold(&self) {
+        self.aq.reset_threshold();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Ring, SCQ};
+    use crate::shared_queue::{TryDequeueError, TryEnqueueError};
+    use std::{sync::Arc, thread};
+
+    #[test]
+    fn basic_ring() {
+        let ring: Ring<16> = Ring::new_full();
+        let mut indices: Vec<usize> = (0..16).map(|_| ring.dequeue().unwrap()).collect();
+        assert_eq!(ring.dequeue(), None);
+        indices.sort();
+        assert_eq!(indices, (0..16).collect::<Vec<_>>());
+
+        let ring: Ring<16> = Ring::new_empty();
+        assert_eq!(ring.dequeue(), None);
+        for i in [3, 1, 2] {
+            assert!(ring.enqueue(i));
+        }
+        assert_eq!(ring.dequeue(), Some(3));
+        assert_eq!(ring.dequeue(), Some(1));
+        assert_eq!(ring.dequeue(), Some(2));
+        assert_eq!(ring.dequeue(), None);
+        ring.close();
+        assert!(!ring.enqueue(0));
+    }
+
+    #[test]
+    fn basic_scq() {
+        let scq: SCQ<i32, 16> = SCQ::new();
+        for i in 0..16 {
+            let item = Box::into_raw(Box::new(i));
+            assert_eq!(scq.enqueue(item), Ok(()));
+        }
+        // SCQ is now full, should fail
+        let item = Box::into_raw(Box::new(16));
+        assert_eq!(
+            scq.enqueue(item),
+            Err(TryEnqueueError::Full(item.cast_const()))
+        );
+        let _ = unsafe { Box::from_raw(item) };
+
+        for i in 0..16 {
+            let value = unsafe { Box::from_raw(scq.dequeue().unwrap()) };
+            assert_eq!(value, Box::new(i));
+        }
+        assert_eq!(scq.dequeue(), Err(TryDequeueError::Empty));
+    }
+
+    #[test]
+    fn scq_reuses_slots() {
+        let scq: SCQ<i32, 16> = SCQ::new();
+        // Dequeueing from an empty ring advances it, the slots must still be usable afterwards
+        for i in 0..100 {
+            assert_eq!(scq.dequeue(), Err(TryDequeueError::Empty));
+            let item = Box::into_raw(Box::new(i));
+            assert_eq!(scq.enqueue(item), Ok(()));
+            let value = unsafe { Box::from_raw(scq.dequeue().unwrap()) };
+            assert_eq!(value, Box::new(i));
+        }
+    }
+
+    #[test]
+    fn scq_concurrent() {
+        const N: usize = 16;
+        let scq: Arc<SCQ<usize, N>> = Arc::new(SCQ::new());
+
+        let mut handles = vec![];
+
+        for i in 0..N {
+            let queue = Arc::clone(&scq);
+            let handle = thread::spawn(move || {
+                let v = Box::into_raw(Box::new(i));
+                queue.enqueue(v).is_ok()
+            });
+            handles.push(handle);
+        }
+
+        for handle in handles {
+            assert!(handle.join().unwrap());
+        }
+
+        let mut dequeue_sum = 0;
+        while let Ok(ptr) = scq.dequeue() {
+            let value = unsafe { Box::from_raw(ptr) };
+            dequeue_sum += *value;
+        }
+
+        // Sum of first n natural numbers (0 to n-1)
+        let expected_sum = N * (N - 1) / 2;
+
+        assert_eq!(expected_sum, dequeue_sum, "Sums do not match!");
+    }
+}
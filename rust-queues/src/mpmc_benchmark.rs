@@ -37,10 +37,9 @@ pub fn benchmark<Q>(
     // Producers
     for _ in 0..nproducer {
         let mut queue_handle = queue.clone();
-        let core_id = core_ids
+        let core_id = *core_ids
             .next()
-            .expect("Ran out of cores! Maybe used fewer threads")
-            .clone();
+            .expect("Ran out of cores! Maybe used fewer threads");
         let handle = thread::spawn(move || {
             let _ = core_affinity::set_for_current(core_id);
             let mut rng = rand::thread_rng();
@@ -51,7 +50,7 @@ pub fn benchmark<Q>(
             - load balances when first segment PRQ reaches 70%
             - Benchmark runs for 1000ms, then stops */
             for j in 0..tops {
-                queue_handle.enqueue((&(j as i32)) as *const _);
+                queue_handle.enqueue(j as i32);
                 if rng.gen_range(0.0..1.0) > congestion_factor {
                     delay_exec();
                 }
@@ -64,10 +63,9 @@ pub fn benchmark<Q>(
     for _ in 0..nconsumer {
         let mut queue_handle = queue.clone();
         let stop_flag_handle = stop_flag.clone();
-        let core_id = core_ids
+        let core_id = *core_ids
             .next()
-            .expect("Ran out of cores! Maybe used fewer threads")
-            .clone();
+            .expect("Ran out of cores! Maybe used fewer threads");
         let handle = thread::spawn(move || {
             let mut rng = rand::thread_rng();
             let _ = core_affinity::set_for_current(core_id);
@@ -80,7 +78,7 @@ pub fn benchmark<Q>(
                         if stop_flag_handle.load(SeqCst) {
                             break;
                         }
-                        backoff = backoff + 1;
+                        backoff += 1;
                         for _ in 0..backoff {
                             delay_exec();
                         }
@@ -1 +1,5 @@
+pub mod msq;
+pub mod msq_arc;
+pub mod msq_epoch;
 pub mod msq_hazp;
+pub mod msq_leak;
@@ -0,0 +1,684 @@
+use crate::{
+    reclaim::{HazardPointers, Reclaimer},
+    shared_queue::{SharedQueue, TryDequeueError, TryEnqueueError},
+};
+use haphazard::HazardPointer;
+use std::{
+    fmt::Debug,
+    mem::MaybeUninit,
+    sync::{
+        atomic::{AtomicUsize, Ordering::SeqCst},
+        Arc,
+    },
+};
+
+pub struct MSQueue<T: 'static, R: Reclaimer = HazardPointers> {
+    queue: Arc<Queue<T, R>>,
+    handle: R::Handle,
+}
+
+impl<T, R: Reclaimer> Clone for MSQueue<T, R> {
+    fn clone(&self) -> Self {
+        Self {
+            queue: self.queue.clone(),
+            handle: R::handle(),
+        }
+    }
+}
+
+impl<T, R: Reclaimer> MSQueue<T, R>
+where
+    T: Send,
+{
+    // A queue that keeps count of its items so len() can be used, at the cost of an extra shared
+    // atomic operation per enqueue and dequeue
+    pub fn new_counted() -> Self {
+        MSQueue {
+            queue: Arc::new(Queue::new_counted()),
+            handle: R::handle(),
+        }
+    }
+
+    // Approximate number of items in the queue, None if it was not created with new_counted
+    pub fn len(&mut self) -> Option<usize> {
+        self.queue.len()
+    }
+
+    pub fn is_empty(&mut self) -> bool {
+        self.queue.is_empty(&mut self.handle)
+    }
+
+    pub fn try_enqueue(&mut self, val: T) -> Result<(), TryEnqueueError<T>> {
+        self.queue.enqueue(val, &mut self.handle)
+    }
+
+    pub fn try_dequeue(&mut self) -> Result<T, TryDequeueError> {
+        self.queue.dequeue(&mut self.handle)
+    }
+
+    // Rejects all further enqueues, consumers can still drain what is left and get
+    // TryDequeueError::Closed once the queue is empty
+    pub fn close(&mut self) {
+        self.queue.close(&mut self.handle)
+    }
+}
+
+impl<T> MSQueue<T, HazardPointers>
+where
+    T: Send + Copy,
+{
+    // Front value without removing it, it stays protected until the handle is used again
+    pub fn peek(&mut self) -> Option<&T> {
+        let [hazp_head, hazp] = &mut self.handle;
+        self.queue.peek(hazp_head, hazp)
+    }
+}
+
+impl<T, R: Reclaimer> SharedQueue<T> for MSQueue<T, R>
+where
+    T: Send,
+{
+    fn new() -> Self {
+        MSQueue {
+            queue: Arc::new(Queue::new()),
+            handle: R::handle(),
+        }
+    }
+    fn enqueue(&mut self, val: T) {
+        let _ = self.try_enqueue(val);
+    }
+
+    fn dequeue(&mut self) -> Option<T> {
+        self.try_dequeue().ok()
+    }
+}
+
+struct Node<T: 'static, R: Reclaimer> {
+    // Owned by the node until it is dequeued. Stored inline so it lives as long as the node, which
+    // keeps peeked values valid. Uninitialized for the initial dummy and the closed marker
+    value: MaybeUninit<T>,
+    next: R::Atomic<Node<T, R>>,
+    closed: bool, // Set for the last node of a closed queue, nothing can be linked after it
+}
+// Unsafe impls of send and sync, the value is only ever taken out by the thread that
+// successfully dequeues the node, and the queue itself is only Send/Sync when T is Send
+unsafe impl<T, R: Reclaimer> Sync for Node<T, R> {}
+unsafe impl<T, R: Reclaimer> Send for Node<T, R> {}
+
+impl<T, R: Reclaimer> Node<T, R> {
+    pub fn new(value: T) -> Node<T, R> {
+        Node {
+            value: MaybeUninit::new(value),
+            next: R::null(),
+            closed: false,
+        }
+    }
+    fn empty() -> Node<T, R> {
+        Node {
+            value: MaybeUninit::uninit(),
+            next: R::null(),
+            closed: false,
+        }
+    }
+    fn closed() -> Node<T, R> {
+        Node {
+            value: MaybeUninit::uninit(),
+            next: R::null(),
+            closed: true,
+        }
+    }
+}
+pub struct Queue<T: 'static, R: Reclaimer = HazardPointers> {
+    head: R::Atomic<Node<T, R>>,
+    tail: R::Atomic<Node<T, R>>,
+    count: Option<AtomicUsize>, // Number of items, only kept if asked for
+}
+
+unsafe impl<T: Send, R: Reclaimer> Send for Queue<T, R> {}
+unsafe impl<T: Send, R: Reclaimer> Sync for Queue<T, R> {}
+
+impl<T, R: Reclaimer> Drop for Queue<T, R> {
+    fn drop(&mut self) {
+        // Empty the queue to drop any leftover items, which leaves just the dummy node and the
+        // closed marker after it if the queue was closed
+        let mut handle = R::handle();
+        loop {
+            match self.dequeue(&mut handle) {
+                Ok(_) => continue,
+                Err(TryDequeueError::Empty) => break,
+                Err(TryDequeueError::Closed) => {
+                    let dummy = R::load_ptr(&self.head);
+                    // Safety: We have exclusive access, and the dummy is never null
+                    unsafe { R::free(&mut (*dummy).next) };
+                    break;
+                }
+            }
+        }
+        unsafe { R::free(&mut self.head) };
+    }
+}
+
+impl<T, R: Reclaimer> Default for Queue<T, R>
+where
+    T: Send,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, R: Reclaimer> Queue<T, R>
+where
+    T: Send,
+{
+    pub fn new() -> Queue<T, R> {
+        let dummy = R::alloc(Node::empty());
+
+        let queue = Queue {
+            head: unsafe { R::atomic(dummy) },
+            tail: unsafe { R::atomic(dummy) },
+            count: None,
+        };
+        unsafe { R::release(dummy) };
+        queue
+    }
+
+    pub fn new_counted() -> Queue<T, R> {
+        let mut queue = Queue::new();
+        queue.count = Some(AtomicUsize::new(0));
+        queue
+    }
+
+    // is_empty needs a handle to look at the head node, so it can not take just &self
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> Option<usize> {
+        self.count.as_ref().map(|count| count.load(SeqCst))
+    }
+
+    pub fn is_empty(&self, handle: &mut R::Handle) -> bool {
+        let mut guard = R::pin(handle);
+        // Safety: Will always point to at least a dummy node, which is protected by the guard
+        let head_node = unsafe { &*R::protect(&mut guard, 0, &self.head) };
+        let next_ptr = R::protect(&mut guard, 1, &head_node.next);
+        // The closed marker is not an item
+        next_ptr.is_null() || unsafe { (*next_ptr).closed }
+    }
+
+    pub fn enqueue(&self, value: T, handle: &mut R::Handle) -> Result<(), TryEnqueueError<T>> {
+        let node_raw = R::alloc(Node::new(value));
+        // Count before linking so a dequeue can never take the count below zero
+        if let Some(count) = &self.count {
+            count.fetch_add(1, SeqCst);
+        }
+        if self.append(node_raw, handle) {
+            Ok(())
+        } else {
+            if let Some(count) = &self.count {
+                count.fetch_sub(1, SeqCst);
+            }
+            // The node was never linked in, so we still own it and its value
+            let value = unsafe { (*node_raw).value.assume_init_read() };
+            unsafe { R::dealloc(node_raw) };
+            Err(TryEnqueueError::Closed(value))
+        }
+    }
+
+    pub fn close(&self, handle: &mut R::Handle) {
+        let node_raw = R::alloc(Node::closed());
+        if !self.append(node_raw, handle) {
+            // Already closed
+            unsafe { R::dealloc(node_raw) };
+        }
+    }
+
+    // Links the node in at the end of the list, returns false if the queue has been closed. The
+    // caller's ownership of the node is given up if it was linked in
+    fn append(&self, node_raw: *mut Node<T, R>, handle: &mut R::Handle) -> bool {
+        let mut guard = R::pin(handle);
+        loop {
+            // Safety: Will always point to at least a dummy node, which is protected by the guard
+            let tail_ptr = R::protect(&mut guard, 0, &self.tail);
+            let tail_node = unsafe { &*tail_ptr };
+
+            if tail_node.closed {
+                return false;
+            }
+
+            let next_ptr = R::protect(&mut guard, 1, &tail_node.next);
+
+            // Check tail snapshot
+            if tail_ptr != R::load_ptr(&self.tail) {
+                continue;
+            }
+
+            // Tail was not pointing to the last node
+            if !next_ptr.is_null() {
+                /* Try to swing tail "forward", i.e. to the "next" node,
+                this will be done until the tail is corrected */
+                let _ = unsafe { R::compare_exchange(&guard, &self.tail, tail_ptr, next_ptr) };
+                continue;
+            }
+
+            // Try link node at the end of linked list
+            if unsafe { R::compare_exchange(&guard, &tail_node.next, next_ptr, node_raw) } {
+                // Try update tail to inserted node
+                unsafe {
+                    R::compare_exchange(&guard, &self.tail, tail_ptr, node_raw);
+                    R::release(node_raw);
+                }
+                return true;
+            }
+        }
+    }
+}
+
+// Drop empties the queue, so dequeue can not require T: Send
+impl<T, R: Reclaimer> Queue<T, R> {
+    pub fn dequeue(&self, handle: &mut R::Handle) -> Result<T, TryDequeueError> {
+        let mut guard = R::pin(handle);
+        loop {
+            // Safety: Will always point to at least a dummy node, which is protected by the guard
+            let head_ptr = R::protect(&mut guard, 0, &self.head);
+            let head_node = unsafe { &*head_ptr };
+
+            let tail_ptr = R::load_ptr(&self.tail);
+
+            let next_ptr = R::protect(&mut guard, 1, &head_node.next);
+
+            // Are head, tail, and next not consistent?
+            if head_ptr != R::load_ptr(&self.head) {
+                continue;
+            }
+
+            // Empty queue
+            if next_ptr.is_null() {
+                return Err(TryDequeueError::Empty);
+            }
+            // Safety: Protected by the guard
+            let next_node = unsafe { &*next_ptr };
+
+            // Only the closed marker is left
+            if next_node.closed {
+                return Err(TryDequeueError::Closed);
+            }
+
+            // Is queue empty or Tail falling behind?
+            if head_ptr == tail_ptr {
+                // Tail is falling behind. Try to advance it
+                let _ = unsafe { R::compare_exchange(&guard, &self.tail, tail_ptr, next_ptr) };
+                continue;
+            }
+
+            assert!(head_ptr != next_ptr);
+
+            if unsafe { R::compare_exchange(&guard, &self.head, head_ptr, next_ptr) } {
+                if let Some(count) = &self.count {
+                    count.fetch_sub(1, SeqCst);
+                }
+                // Safety: Only the thread that won the CAS takes the value out of the new dummy,
+                // which is still protected by the guard
+                let value = unsafe { next_node.value.assume_init_read() };
+                // The node is now dequeued, so we can retire the pointer
+                unsafe { R::retire(&guard, head_ptr) };
+                return Ok(value);
+            }
+        }
+    }
+}
+
+impl<T> Queue<T, HazardPointers>
+where
+    T: Send + Copy,
+{
+    /// Returns the value at the front of the queue without removing it. The node holding it stays
+    /// protected by `hazp` for as long as the reference lives, `hazp_head` is only used during
+    /// the call.
+    ///
+    /// Only available for `Copy` values: a concurrent dequeue hands the value to another thread,
+    /// which is only harmless if the value owns nothing that the new owner could free. Other
+    /// reclamation schemes have no protection that outlives a single operation, so this is only
+    /// available with hazard pointers.
+    pub fn peek<'hp>(
+        &self,
+        hazp_head: &mut HazardPointer,
+        hazp: &'hp mut HazardPointer,
+    ) -> Option<&'hp T> {
+        loop {
+            // Safety: Will always point to at least a dummy node
+            let head_node = self.head.safe_load(hazp_head).unwrap();
+            let head_ptr: *const Node<T, HazardPointers> = head_node;
+
+            let next_ptr = hazp.protect_ptr(unsafe { head_node.next.as_std() });
+
+            // Has the front node been dequeued in the meantime?
+            if head_ptr != self.head.load_ptr() {
+                continue;
+            }
+
+            // Safety: Protected by hazp, which is borrowed for as long as the reference lives
+            let next_node: &'hp Node<T, HazardPointers> = unsafe { next_ptr?.0.as_ref() };
+            if next_node.closed {
+                return None;
+            }
+            return Some(unsafe { next_node.value.assume_init_ref() });
+        }
+    }
+}
+
+impl<T: Debug, R: Reclaimer> Queue<T, R> {
+    /// Debug function to print the queue's current state
+    pub fn debug_print(&self) {
+        unsafe {
+            let mut current = R::load_ptr(&self.head);
+
+            // Check if the queue is empty
+            if R::load_ptr(&(*current).next).is_null() {
+                println!("Queue is empty");
+                return;
+            }
+
+            while !current.is_null() {
+                println!(
+                    "Value: {:?}, Pointer: {:?}",
+                    (*current).value.as_ptr(),
+                    current as *const _
+                );
+                current = R::load_ptr(&(*current).next);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Queue;
+    use crate::{
+        reclaim::{Aarc, Epoch, HazardPointers, Ibr, Leak, Reclaimer},
+        shared_queue::{TryDequeueError, TryEnqueueError},
+    };
+    use core::time;
+    use haphazard::HazardPointer;
+    use rand::Rng;
+    use std::sync::Arc;
+    use std::thread;
+
+    // Every test except the peeking ones runs once for each reclamation scheme
+    macro_rules! reclaimer_tests {
+        ($($name:ident),*) => {
+            $(
+                mod $name {
+                    #[test]
+                    fn hazard_pointers() {
+                        super::$name::<super::HazardPointers>();
+                    }
+                    #[test]
+                    fn epoch() {
+                        super::$name::<super::Epoch>();
+                    }
+                    #[test]
+                    fn aarc() {
+                        super::$name::<super::Aarc>();
+                    }
+                    #[test]
+                    fn leak() {
+                        super::$name::<super::Leak>();
+                    }
+                    #[test]
+                    fn ibr() {
+                        super::$name::<super::Ibr>();
+                    }
+                }
+            )*
+        };
+    }
+
+    reclaimer_tests!(
+        basics,
+        basic_concurrent,
+        concurrent_dequeue_enqueue,
+        dropping_drops_leftover_items,
+        close_drains_then_reports_closed,
+        close_concurrent,
+        len
+    );
+
+    fn basics<R: Reclaimer>() {
+        let queue = Queue::<_, R>::new();
+        let mut handle = R::handle();
+
+        // Populate queue
+        queue.enqueue(0, &mut handle).unwrap();
+        queue.enqueue(1, &mut handle).unwrap();
+        queue.enqueue(2, &mut handle).unwrap();
+
+        // Normal removal
+        assert_eq!(queue.dequeue(&mut handle).unwrap(), 0);
+        assert_eq!(queue.dequeue(&mut handle).unwrap(), 1);
+
+        // Dequeue after dequeues
+        queue.enqueue(3, &mut handle).unwrap();
+        queue.enqueue(4, &mut handle).unwrap();
+
+        // Normal removal to exhaustion
+        assert_eq!(queue.dequeue(&mut handle).unwrap(), 2);
+        assert_eq!(queue.dequeue(&mut handle).unwrap(), 3);
+        assert_eq!(queue.dequeue(&mut handle).unwrap(), 4);
+        assert_eq!(queue.dequeue(&mut handle), Err(TryDequeueError::Empty));
+
+        // Check the exhaustion case fixed the pointer right
+        queue.enqueue(5, &mut handle).unwrap();
+        queue.enqueue(6, &mut handle).unwrap();
+
+        // Normal removal again
+        assert_eq!(queue.dequeue(&mut handle).unwrap(), 5);
+        assert_eq!(queue.dequeue(&mut handle).unwrap(), 6);
+        assert_eq!(queue.dequeue(&mut handle), Err(TryDequeueError::Empty));
+    }
+
+    fn basic_concurrent<R: Reclaimer>() {
+        let queue = Arc::new(Queue::<_, R>::new());
+        let mut handles = vec![];
+
+        let n = 10;
+
+        for i in 0..n {
+            let queue = Arc::clone(&queue);
+            let handle = thread::spawn(move || {
+                let mut handle = R::handle();
+                queue.enqueue(i, &mut handle).unwrap();
+            });
+            handles.push(handle);
+        }
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let mut handle = R::handle();
+        let mut dequeue_sum = 0;
+        while let Ok(value) = queue.dequeue(&mut handle) {
+            dequeue_sum += value;
+        }
+
+        // Sum of first n natural numbers (0 to n-1)
+        let expected_sum = n * (n - 1) / 2;
+
+        assert_eq!(expected_sum, dequeue_sum, "Sums do not match!");
+    }
+
+    fn concurrent_dequeue_enqueue<R: Reclaimer>() {
+        let queue = Arc::new(Queue::<_, R>::new());
+        let mut handles = vec![];
+        let mut rng = rand::thread_rng();
+
+        let n = 10;
+
+        for i in 0..n {
+            // Random number to simulate "do other work" time
+            let rt = rng.gen_range(50..150);
+            let dur = time::Duration::from_nanos(rt);
+
+            let queue = Arc::clone(&queue);
+            let handle = thread::spawn(move || {
+                let mut handle = R::handle();
+                queue.enqueue(i, &mut handle).unwrap();
+                thread::sleep(dur);
+                let _v = queue.dequeue(&mut handle).unwrap();
+            });
+            handles.push(handle);
+        }
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        // Should be empty
+        let mut handle = R::handle();
+        assert_eq!(queue.dequeue(&mut handle), Err(TryDequeueError::Empty));
+    }
+
+    fn dropping_drops_leftover_items<R: Reclaimer>() {
+        let item = Arc::new(());
+        let queue = Queue::<_, R>::new();
+        let mut handle = R::handle();
+        for _ in 0..10 {
+            queue.enqueue(Arc::clone(&item), &mut handle).unwrap();
+        }
+        drop(queue.dequeue(&mut handle));
+        assert_eq!(Arc::strong_count(&item), 10);
+        drop(queue);
+        assert_eq!(Arc::strong_count(&item), 1);
+    }
+
+    fn close_drains_then_reports_closed<R: Reclaimer>() {
+        let queue = Queue::<_, R>::new();
+        let mut handle = R::handle();
+        for i in 0..5 {
+            queue.enqueue(i, &mut handle).unwrap();
+        }
+        queue.close(&mut handle);
+        assert_eq!(
+            queue.enqueue(5, &mut handle),
+            Err(TryEnqueueError::Closed(5))
+        );
+        // Closing twice is fine
+        queue.close(&mut handle);
+        for i in 0..5 {
+            assert_eq!(queue.dequeue(&mut handle), Ok(i));
+        }
+        assert_eq!(queue.dequeue(&mut handle), Err(TryDequeueError::Closed));
+    }
+
+    fn close_concurrent<R: Reclaimer>() {
+        let queue = Arc::new(Queue::<_, R>::new());
+        let mut handles = vec![];
+
+        for i in 0..4 {
+            let queue = Arc::clone(&queue);
+            let handle = thread::spawn(move || {
+                let mut handle = R::handle();
+                let mut enqueued = 0;
+                for j in 0..100 {
+                    if queue.enqueue(i * 100 + j, &mut handle).is_ok() {
+                        enqueued += 1;
+                    }
+                }
+                enqueued
+            });
+            handles.push(handle);
+        }
+
+        let mut handle = R::handle();
+        let mut dequeued = 0;
+        while dequeued < 50 {
+            if queue.dequeue(&mut handle).is_ok() {
+                dequeued += 1;
+            }
+        }
+        queue.close(&mut handle);
+
+        let enqueued: usize = handles.into_iter().map(|h| h.join().unwrap()).sum();
+        loop {
+            match queue.dequeue(&mut handle) {
+                Ok(_) => dequeued += 1,
+                Err(e) => {
+                    assert_eq!(e, TryDequeueError::Closed);
+                    break;
+                }
+            }
+        }
+        assert_eq!(enqueued, dequeued);
+    }
+
+    fn len<R: Reclaimer>() {
+        let queue = Queue::<_, R>::new_counted();
+        let mut handle = R::handle();
+        assert_eq!(queue.len(), Some(0));
+        assert!(queue.is_empty(&mut handle));
+        for i in 0..5 {
+            queue.enqueue(i, &mut handle).unwrap();
+        }
+        assert_eq!(queue.len(), Some(5));
+        assert!(!queue.is_empty(&mut handle));
+        queue.dequeue(&mut handle).unwrap();
+        assert_eq!(queue.len(), Some(4));
+        queue.close(&mut handle);
+        assert!(queue.enqueue(5, &mut handle).is_err());
+        assert_eq!(queue.len(), Some(4));
+        while queue.dequeue(&mut handle).is_ok() {}
+        assert_eq!(queue.len(), Some(0));
+        assert!(queue.is_empty(&mut handle));
+
+        let uncounted: Queue<i32, R> = Queue::<_, R>::new();
+        assert_eq!(uncounted.len(), None);
+        assert!(uncounted.is_empty(&mut handle));
+    }
+
+    #[test]
+    fn peek() {
+        let queue = Queue::<_, HazardPointers>::new();
+        let mut handle = HazardPointers::handle();
+        let mut hazp_head = HazardPointer::new();
+        let mut hazp_peek = HazardPointer::new();
+        assert_eq!(queue.peek(&mut hazp_head, &mut hazp_peek), None);
+        queue.enqueue(1, &mut handle).unwrap();
+        queue.enqueue(2, &mut handle).unwrap();
+        let front = queue.peek(&mut hazp_head, &mut hazp_peek).unwrap();
+        // The node stays protected while it is dequeued
+        assert_eq!(queue.dequeue(&mut handle), Ok(1));
+        assert_eq!(*front, 1);
+        assert_eq!(queue.peek(&mut hazp_head, &mut hazp_peek), Some(&2));
+        queue.close(&mut handle);
+        assert_eq!(queue.dequeue(&mut handle), Ok(2));
+        assert_eq!(queue.peek(&mut hazp_head, &mut hazp_peek), None);
+    }
+
+    #[test]
+    fn peek_concurrent() {
+        let queue = Arc::new(Queue::<_, HazardPointers>::new());
+        let mut handles = vec![];
+
+        for _ in 0..2 {
+            let queue = Arc::clone(&queue);
+            let handle = thread::spawn(move || {
+                let mut handle = HazardPointers::handle();
+                for i in 0..1000 {
+                    queue.enqueue(i, &mut handle).unwrap();
+                    queue.dequeue(&mut handle).unwrap();
+                }
+            });
+            handles.push(handle);
+        }
+
+        let mut hazp_head = HazardPointer::new();
+        let mut hazp = HazardPointer::new();
+        for _ in 0..1000 {
+            if let Some(v) = queue.peek(&mut hazp_head, &mut hazp) {
+                assert!((0..1000).contains(v));
+            }
+        }
+        for handle in handles {
+            handle.join().unwrap();
+        }
+    }
+}
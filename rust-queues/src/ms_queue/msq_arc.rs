@@ -0,0 +1,5 @@
+use crate::reclaim::Aarc;
+
+// Michael-Scott queue with its nodes reclaimed by atomic Arcs from aarc
+pub type MSQueue<T> = super::msq::MSQueue<T, Aarc>;
+pub type Queue<T> = super::msq::Queue<T, Aarc>;
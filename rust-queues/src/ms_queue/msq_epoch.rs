@@ -0,0 +1,5 @@
+use crate::reclaim::Epoch;
+
+// Michael-Scott queue with its nodes reclaimed by crossbeam-epoch
+pub type MSQueue<T> = super::msq::MSQueue<T, Epoch>;
+pub type Queue<T> = super::msq::Queue<T, Epoch>;
@@ -0,0 +1,5 @@
+use crate::reclaim::Leak;
+
+// Michael-Scott queue with its nodes reclaimed by nothing, dequeued nodes are leaked
+pub type MSQueue<T> = super::msq::MSQueue<T, Leak>;
+pub type Queue<T> = super::msq::Queue<T, Leak>;
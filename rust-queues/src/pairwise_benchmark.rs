@@ -29,16 +29,15 @@ pub fn benchmark<Q>(
 
     for _i in 0..nprocs {
         let mut queue_handle = queue.clone();
-        let core_id = core_ids
+        let core_id = *core_ids
             .next()
-            .expect("Ran out of cores! Maybe used fewer threads")
-            .clone();
+            .expect("Ran out of cores! Maybe used fewer threads");
         let handle = thread::spawn(move || {
             let _ = core_affinity::set_for_current(core_id);
             let mut rng = rand::thread_rng();
 
             for j in 0..tops {
-                queue_handle.enqueue((&(j as i32)) as *const _);
+                queue_handle.enqueue(j as i32);
                 if rng.gen_range(0.0..1.0) > congestion_factor {
                     delay_exec();
                 }
@@ -0,0 +1,495 @@
+use std::{
+    mem::ManuallyDrop,
+    ops::Deref,
+    ptr::{self, null_mut},
+    sync::{atomic::Ordering::SeqCst, Arc},
+};
+
+use aarc::{AsPtr, AtomicArc, Snapshot};
+use crossbeam_epoch::{self as epoch, Shared};
+use haphazard::{raw::Pointer, Domain, HazardPointer};
+
+use crate::{ibr, sync::atomic::AtomicPtr};
+
+/// A memory reclamation scheme for the nodes or segments of a linked queue.
+///
+/// Everything is done on raw pointers so the queues can be written once for all schemes. A pointer
+/// loaded with [`Reclaimer::protect`] can be dereferenced until the guard it was protected
+/// through is dropped or the same slot is protected again.
+pub trait Reclaimer: Sized + 'static {
+    /// An atomic pointer to a `U` managed by the scheme
+    type Atomic<U: 'static>;
+    /// State a queue handle keeps between operations, such as its hazard pointers
+    type Handle: Send;
+    /// Held for the duration of one operation, with two slots to protect pointers in
+    type Guard<'h, U: 'static>;
+
+    fn handle() -> Self::Handle;
+    fn pin<U: 'static>(handle: &mut Self::Handle) -> Self::Guard<'_, U>;
+
+    fn null<U: 'static>() -> Self::Atomic<U>;
+    /// Allocates the value, the pointer is owned by the caller until it is released or dealloced
+    fn alloc<U: 'static>(value: U) -> *mut U;
+    /// # Safety
+    /// The pointer must come from alloc and still be owned by the caller
+    unsafe fn atomic<U: 'static>(ptr: *mut U) -> Self::Atomic<U>;
+    /// Gives up the caller's ownership of a pointer that has been stored in an atomic
+    ///
+    /// # Safety
+    /// The pointer must come from alloc and still be owned by the caller
+    unsafe fn release<U: 'static>(ptr: *mut U);
+    /// Frees a pointer that was never stored in an atomic
+    ///
+    /// # Safety
+    /// The pointer must come from alloc and still be owned by the caller
+    unsafe fn dealloc<U: 'static>(ptr: *mut U);
+
+    /// Loads the atomic and protects the pointee in slot 0 or 1 of the guard
+    fn protect<U: 'static>(
+        guard: &mut Self::Guard<'_, U>,
+        slot: usize,
+        atomic: &Self::Atomic<U>,
+    ) -> *mut U;
+    /// Loads the atomic without protecting the pointee, it must not be dereferenced
+    fn load_ptr<U: 'static>(atomic: &Self::Atomic<U>) -> *mut U;
+    /// Returns true if the atomic held current and now holds new
+    ///
+    /// # Safety
+    /// Both pointers must be null, protected by the guard or owned by the caller
+    unsafe fn compare_exchange<U: 'static>(
+        guard: &Self::Guard<'_, U>,
+        atomic: &Self::Atomic<U>,
+        current: *mut U,
+        new: *mut U,
+    ) -> bool;
+    /// Frees a pointer once no other thread can be using it anymore
+    ///
+    /// # Safety
+    /// The pointer must have been unlinked by a successful compare_exchange of this thread and
+    /// can not be reached from any atomic anymore
+    unsafe fn retire<U: 'static>(guard: &Self::Guard<'_, U>, ptr: *mut U);
+    /// Like retire, but hands the pointer to [`Recycle::recycle`] instead of freeing it. Schemes
+    /// that never reclaim a pointer on behalf of the queue drop it as usual
+    ///
+    /// # Safety
+    /// Same as for retire
+    unsafe fn recycle<U: Recycle>(guard: &Self::Guard<'_, U>, ptr: *mut U);
+    /// Frees the pointee of an atomic, once for every allocation left when a queue is dropped
+    ///
+    /// # Safety
+    /// Nobody else can be using the pointee, and the atomic must not be used afterwards
+    unsafe fn free<U: 'static>(atomic: &mut Self::Atomic<U>);
+}
+
+/// An allocation that is handed back to its owner for reuse once it has been reclaimed, see
+/// [`Reclaimer::recycle`].
+pub trait Recycle: Sized + 'static {
+    /// Takes over an allocation that nobody else can be using anymore. It has to be reused or
+    /// freed with the dealloc of the scheme it was allocated with
+    ///
+    /// # Safety
+    /// The pointer must come from the alloc of a reclaimer and not be reachable anymore
+    unsafe fn recycle(ptr: *mut Self);
+}
+
+/// Hazard pointers from haphazard, in the global domain
+pub struct HazardPointers;
+
+// Lets retired pointers through the Send bound of the domain, the queues are only shared between
+// threads when their values are Send
+#[repr(transparent)]
+struct AssertSend<U>(U);
+unsafe impl<U> Send for AssertSend<U> {}
+
+// Pointer type for haphazard that recycles the pointee when the domain drops it
+struct Recycled<U: Recycle>(*mut AssertSend<U>);
+
+impl<U: Recycle> Deref for Recycled<U> {
+    type Target = AssertSend<U>;
+
+    fn deref(&self) -> &Self::Target {
+        // Safety: The pointer is valid until it is recycled
+        unsafe { &*self.0 }
+    }
+}
+
+unsafe impl<U: Recycle> Pointer<AssertSend<U>> for Recycled<U> {
+    fn into_raw(self) -> *mut AssertSend<U> {
+        ManuallyDrop::new(self).0
+    }
+
+    unsafe fn from_raw(ptr: *mut AssertSend<U>) -> Self {
+        Recycled(ptr)
+    }
+}
+
+impl<U: Recycle> Drop for Recycled<U> {
+    fn drop(&mut self) {
+        // Safety: The domain only drops the pointer once no hazard pointer protects it
+        unsafe { U::recycle(self.0.cast()) };
+    }
+}
+
+impl Reclaimer for HazardPointers {
+    type Atomic<U: 'static> = haphazard::AtomicPtr<U>;
+    type Handle = [HazardPointer<'static>; 2];
+    type Guard<'h, U: 'static> = &'h mut [HazardPointer<'static>; 2];
+
+    fn handle() -> Self::Handle {
+        [HazardPointer::new(), HazardPointer::new()]
+    }
+
+    fn pin<U: 'static>(handle: &mut Self::Handle) -> Self::Guard<'_, U> {
+        handle
+    }
+
+    fn null<U: 'static>() -> Self::Atomic<U> {
+        unsafe { haphazard::AtomicPtr::new(null_mut()) }
+    }
+
+    fn alloc<U: 'static>(value: U) -> *mut U {
+        Box::into_raw(Box::new(value))
+    }
+
+    unsafe fn atomic<U: 'static>(ptr: *mut U) -> Self::Atomic<U> {
+        haphazard::AtomicPtr::new(ptr)
+    }
+
+    unsafe fn release<U: 'static>(_ptr: *mut U) {}
+
+    unsafe fn dealloc<U: 'static>(ptr: *mut U) {
+        drop(Box::from_raw(ptr));
+    }
+
+    fn protect<U: 'static>(
+        guard: &mut Self::Guard<'_, U>,
+        slot: usize,
+        atomic: &Self::Atomic<U>,
+    ) -> *mut U {
+        guard[slot]
+            .protect_ptr(unsafe { atomic.as_std() })
+            .map_or(null_mut(), |(ptr, _)| ptr.as_ptr())
+    }
+
+    fn load_ptr<U: 'static>(atomic: &Self::Atomic<U>) -> *mut U {
+        atomic.load_ptr()
+    }
+
+    unsafe fn compare_exchange<U: 'static>(
+        _guard: &Self::Guard<'_, U>,
+        atomic: &Self::Atomic<U>,
+        current: *mut U,
+        new: *mut U,
+    ) -> bool {
+        atomic
+            .as_std()
+            .compare_exchange(current, new, SeqCst, SeqCst)
+            .is_ok()
+    }
+
+    unsafe fn retire<U: 'static>(_guard: &Self::Guard<'_, U>, ptr: *mut U) {
+        Domain::global().retire_ptr::<AssertSend<U>, Box<AssertSend<U>>>(ptr.cast());
+    }
+
+    unsafe fn recycle<U: Recycle>(_guard: &Self::Guard<'_, U>, ptr: *mut U) {
+        Domain::global().retire_ptr::<AssertSend<U>, Recycled<U>>(ptr.cast());
+    }
+
+    unsafe fn free<U: 'static>(atomic: &mut Self::Atomic<U>) {
+        drop(Box::from_raw(atomic.load_ptr()));
+    }
+}
+
+/// Epoch based reclamation from crossbeam-epoch, pinned for every operation
+pub struct Epoch;
+
+impl Reclaimer for Epoch {
+    type Atomic<U: 'static> = epoch::Atomic<U>;
+    type Handle = ();
+    type Guard<'h, U: 'static> = epoch::Guard;
+
+    fn handle() -> Self::Handle {}
+
+    fn pin<U: 'static>(_handle: &mut Self::Handle) -> Self::Guard<'_, U> {
+        epoch::pin()
+    }
+
+    fn null<U: 'static>() -> Self::Atomic<U> {
+        epoch::Atomic::null()
+    }
+
+    // Sized values are boxed by crossbeam-epoch as well, so these pointers can be handed to it
+    fn alloc<U: 'static>(value: U) -> *mut U {
+        Box::into_raw(Box::new(value))
+    }
+
+    unsafe fn atomic<U: 'static>(ptr: *mut U) -> Self::Atomic<U> {
+        epoch::Atomic::from(ptr.cast_const())
+    }
+
+    unsafe fn release<U: 'static>(_ptr: *mut U) {}
+
+    unsafe fn dealloc<U: 'static>(ptr: *mut U) {
+        drop(Box::from_raw(ptr));
+    }
+
+    fn protect<U: 'static>(
+        guard: &mut Self::Guard<'_, U>,
+        _slot: usize,
+        atomic: &Self::Atomic<U>,
+    ) -> *mut U {
+        atomic.load(SeqCst, guard).as_raw().cast_mut()
+    }
+
+    fn load_ptr<U: 'static>(atomic: &Self::Atomic<U>) -> *mut U {
+        // Safety: The pointer is not dereferenced
+        atomic
+            .load(SeqCst, unsafe { epoch::unprotected() })
+            .as_raw()
+            .cast_mut()
+    }
+
+    unsafe fn compare_exchange<U: 'static>(
+        guard: &Self::Guard<'_, U>,
+        atomic: &Self::Atomic<U>,
+        current: *mut U,
+        new: *mut U,
+    ) -> bool {
+        atomic
+            .compare_exchange(
+                Shared::from(current.cast_const()),
+                Shared::from(new.cast_const()),
+                SeqCst,
+                SeqCst,
+                guard,
+            )
+            .is_ok()
+    }
+
+    unsafe fn retire<U: 'static>(guard: &Self::Guard<'_, U>, ptr: *mut U) {
+        guard.defer_destroy(Shared::from(ptr.cast_const()));
+    }
+
+    unsafe fn recycle<U: Recycle>(guard: &Self::Guard<'_, U>, ptr: *mut U) {
+        guard.defer_unchecked(move || U::recycle(ptr));
+    }
+
+    unsafe fn free<U: 'static>(atomic: &mut Self::Atomic<U>) {
+        drop(Box::from_raw(Self::load_ptr(atomic)));
+    }
+}
+
+/// Reference counting with the atomic Arcs from aarc. Every atomic holds a count on its pointee,
+/// so there is nothing to retire or free
+pub struct Aarc;
+
+// The aarc atomics work on Arcs, the raw pointers are the ones from Arc::into_raw
+unsafe fn borrow_arc<U>(ptr: *mut U) -> ManuallyDrop<Arc<U>> {
+    ManuallyDrop::new(Arc::from_raw(ptr.cast_const()))
+}
+
+impl Reclaimer for Aarc {
+    type Atomic<U: 'static> = AtomicArc<U>;
+    type Handle = ();
+    type Guard<'h, U: 'static> = [Option<Snapshot<U>>; 2];
+
+    fn handle() -> Self::Handle {}
+
+    fn pin<U: 'static>(_handle: &mut Self::Handle) -> Self::Guard<'_, U> {
+        [None, None]
+    }
+
+    fn null<U: 'static>() -> Self::Atomic<U> {
+        AtomicArc::new(None)
+    }
+
+    fn alloc<U: 'static>(value: U) -> *mut U {
+        Arc::into_raw(Arc::new(value)).cast_mut()
+    }
+
+    unsafe fn atomic<U: 'static>(ptr: *mut U) -> Self::Atomic<U> {
+        AtomicArc::from(&*borrow_arc(ptr))
+    }
+
+    unsafe fn release<U: 'static>(ptr: *mut U) {
+        drop(Arc::from_raw(ptr.cast_const()));
+    }
+
+    unsafe fn dealloc<U: 'static>(ptr: *mut U) {
+        drop(Arc::from_raw(ptr.cast_const()));
+    }
+
+    fn protect<U: 'static>(
+        guard: &mut Self::Guard<'_, U>,
+        slot: usize,
+        atomic: &Self::Atomic<U>,
+    ) -> *mut U {
+        let snapshot: Option<Snapshot<U>> = atomic.load();
+        let ptr = snapshot
+            .as_ref()
+            .map_or(ptr::null(), |s| Snapshot::as_ptr(s));
+        guard[slot] = snapshot;
+        ptr.cast_mut()
+    }
+
+    fn load_ptr<U: 'static>(atomic: &Self::Atomic<U>) -> *mut U {
+        atomic
+            .load::<Snapshot<U>>()
+            .map_or(ptr::null(), |s| Snapshot::as_ptr(&s))
+            .cast_mut()
+    }
+
+    unsafe fn compare_exchange<U: 'static>(
+        _guard: &Self::Guard<'_, U>,
+        atomic: &Self::Atomic<U>,
+        current: *mut U,
+        new: *mut U,
+    ) -> bool {
+        let current = (!current.is_null()).then(|| borrow_arc(current));
+        let new = (!new.is_null()).then(|| borrow_arc(new));
+        atomic
+            .compare_exchange::<Arc<U>, Arc<U>, Snapshot<U>>(current.as_deref(), new.as_deref())
+            .is_ok()
+    }
+
+    unsafe fn retire<U: 'static>(_guard: &Self::Guard<'_, U>, _ptr: *mut U) {}
+
+    unsafe fn recycle<U: Recycle>(_guard: &Self::Guard<'_, U>, _ptr: *mut U) {}
+
+    unsafe fn free<U: 'static>(_atomic: &mut Self::Atomic<U>) {}
+}
+
+/// Never frees anything that has been shared, as a baseline for the cost of reclamation
+pub struct Leak;
+
+impl Reclaimer for Leak {
+    type Atomic<U: 'static> = AtomicPtr<U>;
+    type Handle = ();
+    type Guard<'h, U: 'static> = ();
+
+    fn handle() -> Self::Handle {}
+
+    fn pin<U: 'static>(_handle: &mut Self::Handle) -> Self::Guard<'_, U> {}
+
+    fn null<U: 'static>() -> Self::Atomic<U> {
+        AtomicPtr::new(null_mut())
+    }
+
+    fn alloc<U: 'static>(value: U) -> *mut U {
+        Box::into_raw(Box::new(value))
+    }
+
+    unsafe fn atomic<U: 'static>(ptr: *mut U) -> Self::Atomic<U> {
+        AtomicPtr::new(ptr)
+    }
+
+    unsafe fn release<U: 'static>(_ptr: *mut U) {}
+
+    unsafe fn dealloc<U: 'static>(ptr: *mut U) {
+        drop(Box::from_raw(ptr));
+    }
+
+    fn protect<U: 'static>(
+        _guard: &mut Self::Guard<'_, U>,
+        _slot: usize,
+        atomic: &Self::Atomic<U>,
+    ) -> *mut U {
+        atomic.load(SeqCst)
+    }
+
+    fn load_ptr<U: 'static>(atomic: &Self::Atomic<U>) -> *mut U {
+        atomic.load(SeqCst)
+    }
+
+    unsafe fn compare_exchange<U: 'static>(
+        _guard: &Self::Guard<'_, U>,
+        atomic: &Self::Atomic<U>,
+        current: *mut U,
+        new: *mut U,
+    ) -> bool {
+        atomic
+            .compare_exchange(current, new, SeqCst, SeqCst)
+            .is_ok()
+    }
+
+    unsafe fn retire<U: 'static>(_guard: &Self::Guard<'_, U>, _ptr: *mut U) {}
+
+    unsafe fn recycle<U: Recycle>(_guard: &Self::Guard<'_, U>, _ptr: *mut U) {}
+
+    unsafe fn free<U: 'static>(_atomic: &mut Self::Atomic<U>) {}
+}
+
+/// Interval based reclamation from the ibr module
+pub struct Ibr;
+
+impl Reclaimer for Ibr {
+    type Atomic<U: 'static> = AtomicPtr<U>;
+    type Handle = ibr::Handle;
+    type Guard<'h, U: 'static> = ibr::Guard<'h>;
+
+    fn handle() -> Self::Handle {
+        ibr::Handle::new()
+    }
+
+    fn pin<U: 'static>(handle: &mut Self::Handle) -> Self::Guard<'_, U> {
+        handle.pin()
+    }
+
+    fn null<U: 'static>() -> Self::Atomic<U> {
+        AtomicPtr::new(null_mut())
+    }
+
+    fn alloc<U: 'static>(value: U) -> *mut U {
+        ibr::alloc(value)
+    }
+
+    unsafe fn atomic<U: 'static>(ptr: *mut U) -> Self::Atomic<U> {
+        AtomicPtr::new(ptr)
+    }
+
+    unsafe fn release<U: 'static>(_ptr: *mut U) {}
+
+    unsafe fn dealloc<U: 'static>(ptr: *mut U) {
+        ibr::dealloc(ptr);
+    }
+
+    fn protect<U: 'static>(
+        guard: &mut Self::Guard<'_, U>,
+        _slot: usize,
+        atomic: &Self::Atomic<U>,
+    ) -> *mut U {
+        guard.protect(atomic)
+    }
+
+    fn load_ptr<U: 'static>(atomic: &Self::Atomic<U>) -> *mut U {
+        atomic.load(SeqCst)
+    }
+
+    unsafe fn compare_exchange<U: 'static>(
+        _guard: &Self::Guard<'_, U>,
+        atomic: &Self::Atomic<U>,
+        current: *mut U,
+        new: *mut U,
+    ) -> bool {
+        atomic
+            .compare_exchange(current, new, SeqCst, SeqCst)
+            .is_ok()
+    }
+
+    unsafe fn retire<U: 'static>(guard: &Self::Guard<'_, U>, ptr: *mut U) {
+        guard.retire(ptr);
+    }
+
+    unsafe fn recycle<U: Recycle>(guard: &Self::Guard<'_, U>, ptr: *mut U) {
+        guard.retire_with(ptr, recycle_erased::<U>);
+    }
+
+    unsafe fn free<U: 'static>(atomic: &mut Self::Atomic<U>) {
+        ibr::dealloc(atomic.load(SeqCst));
+    }
+}
+
+// The ibr allocation starts with the value, so its pointer is also the one from alloc
+unsafe fn recycle_erased<U: Recycle>(ptr: *mut u8) {
+    U::recycle(ptr.cast());
+}
@@ -1,6 +1,87 @@
+use std::{error::Error, fmt};
+
 // Trait for a single queue that can be shared between threads
+//
+// The queue takes ownership of enqueued values and hands them back on dequeue, any values left
+// in the queue are dropped together with the last handle. Queues that can be closed drop values
+// enqueued after closing, use their try_enqueue to get the value back.
 pub trait SharedQueue<T> {
     fn new() -> Self;
-    fn enqueue(&mut self, val: *const T);
-    fn dequeue(&mut self) -> Option<*const T>;
+    fn enqueue(&mut self, val: T);
+    fn dequeue(&mut self) -> Option<T>;
 }
+
+// Queues that can enqueue and dequeue several values with a single operation
+pub trait BatchQueue<T>: SharedQueue<T> {
+    fn enqueue_batch(&mut self, vals: Vec<T>);
+    // Dequeues up to max values into out, returns how many were dequeued
+    fn dequeue_batch(&mut self, out: &mut Vec<T>, max: usize) -> usize;
+}
+
+/// Returned by a failed enqueue, hands the value back to the caller
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub enum TryEnqueueError<T> {
+    /// The queue is at capacity, the enqueue may succeed once items have been dequeued
+    Full(T),
+    /// The queue has been closed and will never accept the value
+    Closed(T),
+}
+
+impl<T> TryEnqueueError<T> {
+    pub fn into_inner(self) -> T {
+        match self {
+            TryEnqueueError::Full(val) | TryEnqueueError::Closed(val) => val,
+        }
+    }
+
+    pub fn is_closed(&self) -> bool {
+        matches!(self, TryEnqueueError::Closed(_))
+    }
+}
+
+// Manual impl so values without Debug can still be unwrapped
+impl<T> fmt::Debug for TryEnqueueError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TryEnqueueError::Full(_) => f.write_str("Full(..)"),
+            TryEnqueueError::Closed(_) => f.write_str("Closed(..)"),
+        }
+    }
+}
+
+impl<T> fmt::Display for TryEnqueueError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TryEnqueueError::Full(_) => f.write_str("enqueueing on a full queue"),
+            TryEnqueueError::Closed(_) => f.write_str("enqueueing on a closed queue"),
+        }
+    }
+}
+
+impl<T> Error for TryEnqueueError<T> {}
+
+/// Returned by a failed dequeue
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TryDequeueError {
+    /// The queue is currently empty, items may still be enqueued later
+    Empty,
+    /// The queue is empty and closed, no more items will ever be dequeued
+    Closed,
+}
+
+impl TryDequeueError {
+    pub fn is_closed(&self) -> bool {
+        matches!(self, TryDequeueError::Closed)
+    }
+}
+
+impl fmt::Display for TryDequeueError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TryDequeueError::Empty => f.write_str("dequeueing from an empty queue"),
+            TryDequeueError::Closed => f.write_str("dequeueing from an empty and closed queue"),
+        }
+    }
+}
+
+impl Error for TryDequeueError {}
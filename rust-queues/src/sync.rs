@@ -0,0 +1,31 @@
+// Atomics of the PRQ and LPRQ, swapped for the loom ones under `--cfg loom` so the model checker
+// sees every access. Global state like the thread id counter or the ibr epoch stays on std
+// atomics, loom ones can only be created inside a model
+
+#[cfg(loom)]
+pub(crate) mod atomic {
+    pub(crate) use loom::sync::atomic::{AtomicPtr, AtomicUsize};
+}
+// The last handle of a queue drops it, loom has to see that handoff too
+#[cfg(loom)]
+pub(crate) use loom::sync::Arc;
+// Loom threads all run on one OS thread, so their thread locals have to come from loom as well
+#[cfg(loom)]
+pub(crate) use loom::thread_local;
+// Lets loom run the other threads while we wait for them, instead of exploring every spin
+#[cfg(loom)]
+pub(crate) use loom::hint::spin_loop;
+
+#[cfg(not(loom))]
+pub(crate) mod atomic {
+    pub(crate) use std::sync::atomic::{AtomicPtr, AtomicUsize};
+}
+#[cfg(not(loom))]
+pub(crate) use std::sync::Arc;
+// The C++ reference spins without a pause, so outside of loom this stays a no-op to keep the
+// benchmarks comparable
+#[cfg(not(loom))]
+#[inline(always)]
+pub(crate) fn spin_loop() {}
+#[cfg(not(loom))]
+pub(crate) use std::thread_local;
@@ -0,0 +1,850 @@
+use crate::shared_queue::SharedQueue;
+use crossbeam_utils::CachePadded;
+use std::{
+    alloc::{self, Layout},
+    hint,
+    ptr::{self, null_mut},
+    sync::{
+        atomic::{fence, AtomicBool, AtomicIsize, AtomicPtr, AtomicUsize, Ordering::SeqCst},
+        Arc,
+    },
+};
+
+// Port of the wait-free queue by Yang and Mellor-Crummey, see wfqueue.c in the C reference. Names
+// follow the reference so the two can be read side by side
+
+// Number of cells in a node, chosen so a node fits in 64 KiB
+const NODE_SIZE: usize = (1 << 10) - 2;
+// How long a dequeuer waits for a slow enqueuer before marking the cell unusable
+const MAX_SPIN: usize = 100;
+// Number of failed fast path attempts before an operation asks for help
+const MAX_PATIENCE: usize = 10;
+// Value of the hazard node id and of Hi when they are not set
+const NONE: usize = usize::MAX;
+
+// Only its address is used, as TOP in the reference: a cell value or request that can no longer be
+// used. Nothing can ever be stored at it, BOT is null
+static TOP: u8 = 0;
+
+fn top<U>() -> *mut U {
+    ptr::addr_of!(TOP).cast_mut().cast()
+}
+
+/// A wait-free MPMC queue. Every handle owns a slot in the queue's list of handles, through which
+/// the other threads help its enqueues and dequeues finish, so each operation completes in a
+/// bounded number of steps.
+///
+/// Slots are never freed while the queue lives, a dropped handle leaves its slot to the next clone.
+pub struct WFQueue<T> {
+    queue: Arc<Queue<T>>,
+    handle: *const Handle<T>,
+}
+
+// The handle pointer is only used by the thread that owns the WFQueue, the queue itself is only
+// Send/Sync when T is Send
+unsafe impl<T: Send> Send for WFQueue<T> {}
+
+impl<T> WFQueue<T> {
+    fn handle(&self) -> &Handle<T> {
+        // Safety: Handles live as long as the queue, which we keep alive
+        unsafe { &*self.handle }
+    }
+}
+
+impl<T> SharedQueue<T> for WFQueue<T> {
+    fn new() -> Self {
+        let queue = Arc::new(Queue::new());
+        let handle = queue.register();
+        WFQueue { queue, handle }
+    }
+
+    fn enqueue(&mut self, val: T) {
+        self.queue
+            .enqueue(self.handle(), Box::into_raw(Box::new(val)))
+    }
+
+    fn dequeue(&mut self) -> Option<T> {
+        let val = self.queue.dequeue(self.handle());
+        // Safety: Every value was boxed by enqueue and is handed out exactly once
+        (!val.is_null()).then(|| *unsafe { Box::from_raw(val) })
+    }
+}
+
+impl<T> Clone for WFQueue<T> {
+    fn clone(&self) -> Self {
+        let queue = self.queue.clone();
+        let handle = queue.register();
+        WFQueue { queue, handle }
+    }
+}
+
+impl<T> Drop for WFQueue<T> {
+    fn drop(&mut self) {
+        // No request of this handle is pending, so the slot can be handed to the next clone as is
+        self.handle().in_use.store(false, SeqCst);
+    }
+}
+
+struct Enq<T> {
+    id: AtomicIsize,
+    val: AtomicPtr<T>,
+}
+
+struct Deq {
+    id: AtomicIsize,
+    idx: AtomicIsize,
+}
+
+#[repr(C)]
+struct Cell<T> {
+    val: AtomicPtr<T>,
+    enq: AtomicPtr<Enq<T>>,
+    deq: AtomicPtr<Deq>,
+    _pad: [usize; 5],
+}
+
+struct Node<T> {
+    next: CachePadded<AtomicPtr<Node<T>>>,
+    id: usize, // Only written before the node is linked in
+    cells: [Cell<T>; NODE_SIZE],
+}
+
+impl<T> Node<T> {
+    // Allocated zeroed directly on the heap, as the node is too large to build on the stack. All
+    // zeroes is id 0 with every pointer null
+    fn new() -> *mut Node<T> {
+        let layout = Layout::new::<Node<T>>();
+        let node = unsafe { alloc::alloc_zeroed(layout) }.cast::<Node<T>>();
+        if node.is_null() {
+            alloc::handle_alloc_error(layout);
+        }
+        node
+    }
+
+    // Safety: The node must have been allocated by new and not be reachable anymore
+    unsafe fn free(node: *mut Node<T>) {
+        drop(Box::from_raw(node));
+    }
+}
+
+// Per handle state of the reference's handle_t. Everything but the requests is only written by
+// the thread using the handle, except for Ep and Dp which cleanup moves forward
+struct Handle<T> {
+    next: AtomicPtr<Handle<T>>,
+    hzd_node_id: AtomicUsize,
+    ep: AtomicPtr<Node<T>>,
+    enq_node_id: AtomicUsize,
+    dp: AtomicPtr<Node<T>>,
+    deq_node_id: AtomicUsize,
+    er: CachePadded<Enq<T>>,
+    dr: CachePadded<Deq>,
+    eh: CachePadded<AtomicPtr<Handle<T>>>,
+    ei: AtomicIsize,
+    dh: AtomicPtr<Handle<T>>,
+    spare: CachePadded<AtomicPtr<Node<T>>>,
+    in_use: AtomicBool,
+}
+
+pub struct Queue<T> {
+    ei: CachePadded<AtomicIsize>,
+    di: CachePadded<AtomicIsize>,
+    hi: CachePadded<AtomicUsize>,
+    hp: AtomicPtr<Node<T>>,
+    handles: AtomicPtr<Handle<T>>,
+    nprocs: AtomicUsize,
+}
+
+unsafe impl<T: Send> Send for Queue<T> {}
+unsafe impl<T: Send> Sync for Queue<T> {}
+
+impl<T> Drop for Queue<T> {
+    fn drop(&mut self) {
+        // We have exclusive access, so free everything directly. A value that was never dequeued
+        // sits in a cell without a dequeuer
+        let mut node = self.hp.load(SeqCst);
+        while !node.is_null() {
+            for cell in unsafe { &(*node).cells } {
+                let val = cell.val.load(SeqCst);
+                if !val.is_null() && val != top() && cell.deq.load(SeqCst).is_null() {
+                    drop(unsafe { Box::from_raw(val) });
+                }
+            }
+            let next = unsafe { (*node).next.load(SeqCst) };
+            unsafe { Node::free(node) };
+            node = next;
+        }
+
+        let first = self.handles.load(SeqCst);
+        let mut handle = first;
+        while !handle.is_null() {
+            let handle_box = unsafe { Box::from_raw(handle) };
+            let spare = handle_box.spare.load(SeqCst);
+            if !spare.is_null() {
+                unsafe { Node::free(spare) };
+            }
+            handle = handle_box.next.load(SeqCst);
+            if handle == first {
+                break;
+            }
+        }
+    }
+}
+
+impl<T> Default for Queue<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Queue<T> {
+    pub fn new() -> Queue<T> {
+        Queue {
+            ei: AtomicIsize::new(1).into(),
+            di: AtomicIsize::new(1).into(),
+            hi: AtomicUsize::new(0).into(),
+            hp: AtomicPtr::new(Node::new()),
+            handles: AtomicPtr::new(null_mut()),
+            nprocs: AtomicUsize::new(0),
+        }
+    }
+
+    // Takes an unused slot in the list of handles, or links in a new one
+    fn register(&self) -> *const Handle<T> {
+        let first = self.handles.load(SeqCst);
+        let mut handle = first;
+        while !handle.is_null() {
+            let h = unsafe { &*handle };
+            if h.in_use
+                .compare_exchange(false, true, SeqCst, SeqCst)
+                .is_ok()
+            {
+                return handle;
+            }
+            handle = h.next.load(SeqCst);
+            if handle == first {
+                break;
+            }
+        }
+
+        // Hold off cleanup, which only knows about linked handles, until ours is linked in and
+        // pointing at Hp
+        let oid = loop {
+            let oid = self.hi.load(SeqCst);
+            if oid != NONE && self.hi.compare_exchange(oid, NONE, SeqCst, SeqCst).is_ok() {
+                break oid;
+            }
+            hint::spin_loop();
+        };
+
+        let hp = self.hp.load(SeqCst);
+        let hp_id = unsafe { (*hp).id };
+        let th = Box::into_raw(Box::new(Handle {
+            next: AtomicPtr::new(null_mut()),
+            hzd_node_id: AtomicUsize::new(NONE),
+            ep: AtomicPtr::new(hp),
+            enq_node_id: AtomicUsize::new(hp_id),
+            dp: AtomicPtr::new(hp),
+            deq_node_id: AtomicUsize::new(hp_id),
+            er: Enq {
+                id: AtomicIsize::new(0),
+                val: AtomicPtr::new(null_mut()),
+            }
+            .into(),
+            dr: Deq {
+                id: AtomicIsize::new(0),
+                idx: AtomicIsize::new(-1),
+            }
+            .into(),
+            eh: AtomicPtr::new(null_mut()).into(),
+            ei: AtomicIsize::new(0),
+            dh: AtomicPtr::new(null_mut()),
+            spare: AtomicPtr::new(Node::new()).into(),
+            in_use: AtomicBool::new(true),
+        }));
+        // Safety: Not shared until it is linked in below
+        let h = unsafe { &*th };
+        self.nprocs.fetch_add(1, SeqCst);
+
+        let mut tail = self.handles.load(SeqCst);
+        if tail.is_null() {
+            h.next.store(th, SeqCst);
+            match self
+                .handles
+                .compare_exchange(null_mut(), th, SeqCst, SeqCst)
+            {
+                Ok(_) => {
+                    h.eh.store(th, SeqCst);
+                    h.dh.store(th, SeqCst);
+                    self.hi.store(oid, SeqCst);
+                    return th;
+                }
+                Err(current) => tail = current,
+            }
+        }
+
+        let tail = unsafe { &*tail };
+        let mut next = tail.next.load(SeqCst);
+        loop {
+            h.next.store(next, SeqCst);
+            match tail.next.compare_exchange(next, th, SeqCst, SeqCst) {
+                Ok(_) => break,
+                Err(current) => next = current,
+            }
+        }
+        h.eh.store(next, SeqCst);
+        h.dh.store(next, SeqCst);
+        self.hi.store(oid, SeqCst);
+        th
+    }
+
+    fn spin(p: &AtomicPtr<T>) -> *mut T {
+        let mut v = p.load(SeqCst);
+        let mut patience = MAX_SPIN;
+        while v.is_null() && patience > 0 {
+            v = p.load(SeqCst);
+            hint::spin_loop();
+            patience -= 1;
+        }
+        v
+    }
+
+    // Moves cur back to the oldest node the hazard node id could still be using
+    unsafe fn check(
+        p_hzd_node_id: &AtomicUsize,
+        mut cur: *mut Node<T>,
+        old: *mut Node<T>,
+    ) -> *mut Node<T> {
+        let hzd_node_id = p_hzd_node_id.load(SeqCst);
+        if hzd_node_id < (*cur).id {
+            let mut tmp = old;
+            while (*tmp).id < hzd_node_id {
+                tmp = (*tmp).next.load(SeqCst);
+            }
+            cur = tmp;
+        }
+        cur
+    }
+
+    // Moves the node pointer of a handle forward to cur, or cur back to it if that fails
+    unsafe fn update(
+        p_pn: &AtomicPtr<Node<T>>,
+        mut cur: *mut Node<T>,
+        p_hzd_node_id: &AtomicUsize,
+        old: *mut Node<T>,
+    ) -> *mut Node<T> {
+        let ptr = p_pn.load(SeqCst);
+        if (*ptr).id < (*cur).id {
+            if let Err(ptr) = p_pn.compare_exchange(ptr, cur, SeqCst, SeqCst) {
+                if (*ptr).id < (*cur).id {
+                    cur = ptr;
+                }
+            }
+            cur = Self::check(p_hzd_node_id, cur, old);
+        }
+        cur
+    }
+
+    // Frees the nodes no handle can reach anymore, once there are enough of them
+    fn cleanup(&self, th: &Handle<T>) {
+        let oid = self.hi.load(SeqCst);
+        let mut new = th.dp.load(SeqCst);
+
+        if oid == NONE {
+            return;
+        }
+        if unsafe { (*new).id } < oid + 2 * self.nprocs.load(SeqCst) {
+            return;
+        }
+        if self.hi.compare_exchange(oid, NONE, SeqCst, SeqCst).is_err() {
+            return;
+        }
+
+        let mut old = self.hp.load(SeqCst);
+        let th_ptr: *const Handle<T> = th;
+        let mut ph = th_ptr;
+        let mut phs: Vec<*const Handle<T>> = Vec::new();
+
+        unsafe {
+            loop {
+                let h = &*ph;
+                new = Self::check(&h.hzd_node_id, new, old);
+                new = Self::update(&h.ep, new, &h.hzd_node_id, old);
+                new = Self::update(&h.dp, new, &h.hzd_node_id, old);
+
+                phs.push(ph);
+                ph = h.next.load(SeqCst);
+                if (*new).id <= oid || ph == th_ptr {
+                    break;
+                }
+            }
+
+            while (*new).id > oid {
+                let Some(ph) = phs.pop() else {
+                    break;
+                };
+                new = Self::check(&(*ph).hzd_node_id, new, old);
+            }
+
+            let nid = (*new).id;
+            if nid <= oid {
+                self.hi.store(oid, SeqCst);
+            } else {
+                self.hp.store(new, SeqCst);
+                self.hi.store(nid, SeqCst);
+
+                while old != new {
+                    let tmp = (*old).next.load(SeqCst);
+                    Node::free(old);
+                    old = tmp;
+                }
+            }
+        }
+    }
+
+    // Walks from ptr to the node holding cell i, appending nodes as needed
+    fn find_cell(&self, ptr: &mut *mut Node<T>, i: isize, th: &Handle<T>) -> &Cell<T> {
+        let i = i as usize;
+        let mut curr = *ptr;
+        let mut j = unsafe { (*curr).id };
+        while j < i / NODE_SIZE {
+            let mut next = unsafe { (*curr).next.load(SeqCst) };
+            if next.is_null() {
+                let mut temp = th.spare.load(SeqCst);
+                if temp.is_null() {
+                    temp = Node::new();
+                    th.spare.store(temp, SeqCst);
+                }
+                // Safety: The spare node is only ever used by this handle and not linked in yet
+                unsafe { (*temp).id = j + 1 };
+
+                match unsafe { (*curr).next.compare_exchange(next, temp, SeqCst, SeqCst) } {
+                    Ok(_) => {
+                        next = temp;
+                        th.spare.store(null_mut(), SeqCst);
+                    }
+                    Err(current) => next = current,
+                }
+            }
+            curr = next;
+            j += 1;
+        }
+        *ptr = curr;
+        // Safety: Nodes from the handle's hazard node id on are not freed
+        unsafe { &(*curr).cells[i % NODE_SIZE] }
+    }
+
+    // Same as find_cell, starting at and updating one of the node pointers of a handle
+    fn find_cell_from(&self, p: &AtomicPtr<Node<T>>, i: isize, th: &Handle<T>) -> &Cell<T> {
+        let mut node = p.load(SeqCst);
+        let cell: *const Cell<T> = self.find_cell(&mut node, i, th);
+        p.store(node, SeqCst);
+        unsafe { &*cell }
+    }
+
+    fn enq_fast(&self, th: &Handle<T>, v: *mut T, id: &mut isize) -> bool {
+        let i = self.ei.fetch_add(1, SeqCst);
+        let c = self.find_cell_from(&th.ep, i, th);
+        if c.val
+            .compare_exchange(null_mut(), v, SeqCst, SeqCst)
+            .is_ok()
+        {
+            return true;
+        }
+        *id = i;
+        false
+    }
+
+    fn enq_slow(&self, th: &Handle<T>, v: *mut T, mut id: isize) {
+        let enq = &th.er;
+        let enq_ptr: *const Enq<T> = &**enq;
+        enq.val.store(v, SeqCst);
+        enq.id.store(id, SeqCst);
+
+        let mut tail = th.ep.load(SeqCst);
+        let mut i;
+        loop {
+            i = self.ei.fetch_add(1, SeqCst);
+            let c = self.find_cell(&mut tail, i, th);
+            if c.enq
+                .compare_exchange(null_mut(), enq_ptr.cast_mut(), SeqCst, SeqCst)
+                .is_ok()
+                && c.val.load(SeqCst) != top()
+            {
+                let _ = enq.id.compare_exchange(id, -i, SeqCst, SeqCst);
+                break;
+            }
+            if enq.id.load(SeqCst) <= 0 {
+                break;
+            }
+        }
+
+        id = -enq.id.load(SeqCst);
+        let c = self.find_cell_from(&th.ep, id, th);
+        if id > i {
+            let mut ei = self.ei.load(SeqCst);
+            while ei <= id {
+                match self.ei.compare_exchange(ei, id + 1, SeqCst, SeqCst) {
+                    Ok(_) => break,
+                    Err(current) => ei = current,
+                }
+            }
+        }
+        c.val.store(v, SeqCst);
+    }
+
+    fn enqueue(&self, th: &Handle<T>, v: *mut T) {
+        th.hzd_node_id.store(th.enq_node_id.load(SeqCst), SeqCst);
+
+        let mut id = 0;
+        let done = (0..=MAX_PATIENCE).any(|_| self.enq_fast(th, v, &mut id));
+        if !done {
+            self.enq_slow(th, v, id);
+        }
+
+        th.enq_node_id
+            .store(unsafe { (*th.ep.load(SeqCst)).id }, SeqCst);
+        th.hzd_node_id.store(NONE, SeqCst);
+    }
+
+    // Makes sure cell i either gets a value or is marked unusable, helping a slow enqueuer if there
+    // is one. Returns the value of the cell, null if the queue is empty, or TOP
+    fn help_enq(&self, th: &Handle<T>, c: &Cell<T>, i: isize) -> *mut T {
+        let mut v = Self::spin(&c.val);
+
+        if (v != top() && !v.is_null())
+            || (v.is_null()
+                && match c.val.compare_exchange(v, top(), SeqCst, SeqCst) {
+                    Ok(_) => false,
+                    Err(current) => {
+                        v = current;
+                        v != top()
+                    }
+                })
+        {
+            return v;
+        }
+
+        let mut e = c.enq.load(SeqCst);
+
+        if e.is_null() {
+            let mut ph = unsafe { &*th.eh.load(SeqCst) };
+            let mut pe: *const Enq<T> = &*ph.er;
+            let mut id = ph.er.id.load(SeqCst);
+
+            if th.ei.load(SeqCst) != 0 && th.ei.load(SeqCst) != id {
+                th.ei.store(0, SeqCst);
+                th.eh.store(ph.next.load(SeqCst), SeqCst);
+                ph = unsafe { &*th.eh.load(SeqCst) };
+                pe = &*ph.er;
+                id = ph.er.id.load(SeqCst);
+            }
+
+            if id > 0
+                && id <= i
+                && match c.enq.compare_exchange(e, pe.cast_mut(), SeqCst, SeqCst) {
+                    Ok(_) => false,
+                    Err(current) => {
+                        e = current;
+                        true
+                    }
+                }
+            {
+                th.ei.store(id, SeqCst);
+            } else {
+                th.eh.store(ph.next.load(SeqCst), SeqCst);
+            }
+
+            if e.is_null() {
+                // Either seals the cell or picks up the request that got there first
+                e = match c.enq.compare_exchange(e, top(), SeqCst, SeqCst) {
+                    Ok(_) => top(),
+                    Err(current) => current,
+                };
+            }
+        }
+
+        if e == top() {
+            return if self.ei.load(SeqCst) <= i {
+                null_mut()
+            } else {
+                top()
+            };
+        }
+
+        // Safety: Requests live in handles, which are never freed while the queue lives
+        let e = unsafe { &*e };
+        let ei = e.id.load(SeqCst);
+        let ev = e.val.load(SeqCst);
+
+        if ei > i {
+            if c.val.load(SeqCst) == top() && self.ei.load(SeqCst) <= i {
+                return null_mut();
+            }
+        } else if (ei > 0 && e.id.compare_exchange(ei, -i, SeqCst, SeqCst).is_ok())
+            || (ei == -i && c.val.load(SeqCst) == top())
+        {
+            let mut ei = self.ei.load(SeqCst);
+            while ei <= i {
+                match self.ei.compare_exchange(ei, i + 1, SeqCst, SeqCst) {
+                    Ok(_) => break,
+                    Err(current) => ei = current,
+                }
+            }
+            c.val.store(ev, SeqCst);
+        }
+
+        c.val.load(SeqCst)
+    }
+
+    // Helps the pending dequeue request of ph, if any, to find a cell
+    fn help_deq(&self, th: &Handle<T>, ph: &Handle<T>) {
+        let deq = &ph.dr;
+        let deq_ptr: *const Deq = &**deq;
+        let mut idx = deq.idx.load(SeqCst);
+        let id = deq.id.load(SeqCst);
+
+        if idx < id {
+            return;
+        }
+
+        let mut dp = ph.dp.load(SeqCst);
+        th.hzd_node_id.store(ph.hzd_node_id.load(SeqCst), SeqCst);
+        fence(SeqCst);
+        idx = deq.idx.load(SeqCst);
+
+        let mut i = id + 1;
+        let mut old = id;
+        let mut new = 0;
+        loop {
+            let mut h = dp;
+            while idx == old && new == 0 {
+                let c = self.find_cell(&mut h, i, th);
+
+                let mut di = self.di.load(SeqCst);
+                while di <= i {
+                    match self.di.compare_exchange(di, i + 1, SeqCst, SeqCst) {
+                        Ok(_) => break,
+                        Err(current) => di = current,
+                    }
+                }
+
+                let v = self.help_enq(th, c, i);
+                if v.is_null() || (v != top() && c.deq.load(SeqCst).is_null()) {
+                    new = i;
+                } else {
+                    idx = deq.idx.load(SeqCst);
+                }
+                i += 1;
+            }
+
+            if new != 0 {
+                idx = match deq.idx.compare_exchange(idx, new, SeqCst, SeqCst) {
+                    Ok(_) => new,
+                    Err(current) => current,
+                };
+                if idx >= new {
+                    new = 0;
+                }
+            }
+
+            if idx < 0 || deq.id.load(SeqCst) != id {
+                break;
+            }
+
+            let c = self.find_cell(&mut dp, idx, th);
+            if c.val.load(SeqCst) == top()
+                || match c
+                    .deq
+                    .compare_exchange(null_mut(), deq_ptr.cast_mut(), SeqCst, SeqCst)
+                {
+                    Ok(_) => true,
+                    Err(current) => current == deq_ptr.cast_mut(),
+                }
+            {
+                let _ = deq.idx.compare_exchange(idx, -idx, SeqCst, SeqCst);
+                break;
+            }
+
+            old = idx;
+            if idx >= i {
+                i = idx + 1;
+            }
+        }
+    }
+
+    fn deq_fast(&self, th: &Handle<T>, id: &mut isize) -> *mut T {
+        let i = self.di.fetch_add(1, SeqCst);
+        let c = self.find_cell_from(&th.dp, i, th);
+        let v = self.help_enq(th, c, i);
+
+        if v.is_null() {
+            return null_mut();
+        }
+        if v != top()
+            && c.deq
+                .compare_exchange(null_mut(), top(), SeqCst, SeqCst)
+                .is_ok()
+        {
+            return v;
+        }
+
+        *id = i;
+        top()
+    }
+
+    fn deq_slow(&self, th: &Handle<T>, id: isize) -> *mut T {
+        let deq = &th.dr;
+        deq.id.store(id, SeqCst);
+        deq.idx.store(id, SeqCst);
+
+        self.help_deq(th, th);
+        let i = -deq.idx.load(SeqCst);
+        let c = self.find_cell_from(&th.dp, i, th);
+        let val = c.val.load(SeqCst);
+
+        if val == top() {
+            null_mut()
+        } else {
+            val
+        }
+    }
+
+    // Returns the dequeued value, or null if the queue is empty
+    fn dequeue(&self, th: &Handle<T>) -> *mut T {
+        th.hzd_node_id.store(th.deq_node_id.load(SeqCst), SeqCst);
+
+        let mut id = 0;
+        let mut v = self.deq_fast(th, &mut id);
+        for _ in 0..MAX_PATIENCE {
+            if v != top() {
+                break;
+            }
+            v = self.deq_fast(th, &mut id);
+        }
+        if v == top() {
+            v = self.deq_slow(th, id);
+        }
+
+        if !v.is_null() {
+            let dh = unsafe { &*th.dh.load(SeqCst) };
+            self.help_deq(th, dh);
+            th.dh.store(dh.next.load(SeqCst), SeqCst);
+        }
+
+        th.deq_node_id
+            .store(unsafe { (*th.dp.load(SeqCst)).id }, SeqCst);
+        th.hzd_node_id.store(NONE, SeqCst);
+
+        if th.spare.load(SeqCst).is_null() {
+            self.cleanup(th);
+            th.spare.store(Node::new(), SeqCst);
+        }
+
+        v
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{sync::Arc, thread};
+
+    use super::{WFQueue, NODE_SIZE};
+    use crate::shared_queue::SharedQueue;
+
+    #[test]
+    fn basic() {
+        let mut queue: WFQueue<i32> = WFQueue::new();
+        assert_eq!(queue.dequeue(), None);
+        for i in 0..100 {
+            queue.enqueue(i);
+        }
+        for i in 0..100 {
+            assert_eq!(queue.dequeue(), Some(i));
+        }
+        assert_eq!(queue.dequeue(), None);
+    }
+
+    #[test]
+    fn spans_nodes() {
+        let mut queue: WFQueue<usize> = WFQueue::new();
+        let mut other = queue.clone();
+        // Enough to link in several nodes and have cleanup free the drained ones
+        for round in 0..8 {
+            for i in 0..NODE_SIZE {
+                queue.enqueue(round * NODE_SIZE + i);
+            }
+            for i in 0..NODE_SIZE {
+                assert_eq!(other.dequeue(), Some(round * NODE_SIZE + i));
+            }
+        }
+        assert_eq!(other.dequeue(), None);
+    }
+
+    #[test]
+    fn reuses_dropped_handles() {
+        let queue: WFQueue<i32> = WFQueue::new();
+        for i in 0..10 {
+            let mut handle = queue.clone();
+            handle.enqueue(i);
+        }
+        assert_eq!(
+            queue.queue.nprocs.load(std::sync::atomic::Ordering::SeqCst),
+            2
+        );
+    }
+
+    #[test]
+    fn basic_concurrent() {
+        let queue: WFQueue<usize> = WFQueue::new();
+
+        let mut producers = vec![];
+        for i in 0..4 {
+            let mut queue = queue.clone();
+            let handle = thread::spawn(move || {
+                for j in 0..1000 {
+                    queue.enqueue(i * 1000 + j);
+                }
+            });
+            producers.push(handle);
+        }
+
+        let mut consumers = vec![];
+        for _ in 0..4 {
+            let mut queue = queue.clone();
+            let handle = thread::spawn(move || {
+                let mut sum = 0;
+                for _ in 0..1000 {
+                    loop {
+                        if let Some(v) = queue.dequeue() {
+                            sum += v;
+                            break;
+                        }
+                    }
+                }
+                sum
+            });
+            consumers.push(handle);
+        }
+
+        for handle in producers {
+            handle.join().unwrap();
+        }
+        let sum: usize = consumers.into_iter().map(|h| h.join().unwrap()).sum();
+        assert_eq!(sum, 4000 * 3999 / 2, "Sums do not match!");
+    }
+
+    #[test]
+    fn dropping_drops_leftover_items() {
+        let item = Arc::new(());
+        let mut queue: WFQueue<Arc<()>> = WFQueue::new();
+        for _ in 0..2000 {
+            queue.enqueue(Arc::clone(&item));
+        }
+        for _ in 0..500 {
+            queue.dequeue().unwrap();
+        }
+        assert_eq!(Arc::strong_count(&item), 1501);
+        drop(queue);
+        assert_eq!(Arc::strong_count(&item), 1);
+    }
+}
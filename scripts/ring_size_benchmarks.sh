@@ -0,0 +1,63 @@
+#!/bin/bash
+
+# Create temporary directory to store intermediate hyperfine results
+source "${SCRIPT_DIR}/setup_temp_dir.sh"
+
+workload="$1"
+merged_file_name="$2"
+
+# Makes sure intermediate files are ordered correctly
+index=1
+
+handle_pairwise() {
+    local r="$1"
+    local i="$2"
+
+    echo "Running pairwise benchmark with ring size: $r"
+
+    hyperfine "$BINARY $THREADS $LOGN $EVEN_CORES 0.0 --ring-size $r" --export-json "$temp_dir/result$i.json"
+
+    # Add the "parameter" field to the JSON
+    python3 "${SCRIPT_DIR}"/add_params.py "$temp_dir/result$i.json" $r "Ring size"
+}
+
+handle_mpmc() {
+    local r="$1"
+    local i="$2"
+    local ratio="$3"
+
+    # Calculate maximum amount of producers & consumers for the ratio
+    max_threads=$(($(nproc) / $FACTOR))
+    IFS=':' read producer_multiplier consumer_multiplier <<< "$ratio"
+    min_threads=$((producer_multiplier + consumer_multiplier))
+    base=$(($max_threads / $min_threads))
+    producers=$(($base * producer_multiplier))
+    consumers=$(($base * consumer_multiplier))
+
+    echo "Running mpmc $ratio benchmark with ring size: $r"
+
+    hyperfine "$BINARY $producers $consumers $LOGN $EVEN_CORES 0.0 --ring-size $r" --export-json "$temp_dir/result$i.json"
+
+    # Add the "parameter" field to the JSON
+    python3 "${SCRIPT_DIR}"/add_params.py "$temp_dir/result$i.json" $r "Ring size"
+}
+
+# The same sizes as ring-size-benchmarks.sh of the C++ reference
+for ring_size in 32 64 256 512 1024 2048 4096 8192; do
+    if [[ "$workload" == "pairwise" ]]; then
+        handle_pairwise "$ring_size" "$index"
+    elif [[ "$workload" =~ ^mpmc ]]; then
+        # Extract the ratio from string
+        ratio="${workload#mpmc-}"
+        handle_mpmc "$ring_size" "$index" "$ratio"
+    else
+        echo "Invalid workload type specified"
+    fi
+    ((index++))
+done
+
+# Merge the individual hyperfine commands into single JSON
+python3 "${SCRIPT_DIR}"/merge_ratios.py "$temp_dir/*.json" "$temp_dir/$merged_file_name"
+
+# Moves the resulting JSON to the result directory
+mv "$temp_dir/$merged_file_name" "${RESULT_DIR}/"